@@ -0,0 +1,113 @@
+//! Self-play arena: plays seeded matches between two bot configurations on the local referee.
+//!
+//! Every seed is played twice with the seats swapped, so neither side profits from a lucky deal.
+
+use std::env;
+use std::process;
+use std::thread;
+
+use cg_fall_2020::referee;
+use cg_fall_2020::BotConfig;
+
+const USAGE: &str = "usage: arena [--games N] [--seed S] [--threads T] [--a key=value]... [--b key=value]...
+
+Both sides start from the default bot settings with verbose=false and no time limit, and stop
+every search after 5000 nodes, so a seed always gives the same result.";
+
+struct Options {
+    games: usize,
+    seed: u64,
+    threads: usize,
+    configs: [BotConfig; 2],
+}
+
+/// One finished match, from side A's point of view.
+struct Outcome {
+    a_score: i32,
+    b_score: i32,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let outcomes = run(&options);
+    report(&outcomes);
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let config = BotConfig::self_play();
+
+    let mut options = Options {
+        games: 100,
+        seed: 0,
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        configs: [config.clone(), config],
+    };
+
+    let mut args = args.into_iter();
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+
+        match &flag[..] {
+            "--games" => options.games = value.parse().map_err(|_| format!("invalid game count: {}", value))?,
+            "--seed" => options.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--threads" => options.threads = value.parse().map_err(|_| format!("invalid thread count: {}", value))?,
+            "--a" => options.configs[0].set_pair(&value)?,
+            "--b" => options.configs[1].set_pair(&value)?,
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn run(options: &Options) -> Vec<Outcome> {
+    let configs = [&options.configs[0], &options.configs[1]];
+
+    referee::play_series(configs, options.games, options.seed, options.threads).into_iter().map(|scores| Outcome {
+        a_score: scores[0],
+        b_score: scores[1],
+    }).collect()
+}
+
+fn report(outcomes: &[Outcome]) {
+    if outcomes.is_empty() {
+        println!("No games played.");
+        return;
+    }
+
+    let wins = outcomes.iter().filter(|outcome| outcome.a_score > outcome.b_score).count();
+    let losses = outcomes.iter().filter(|outcome| outcome.a_score < outcome.b_score).count();
+    let draws = outcomes.len() - wins - losses;
+
+    let points: Vec<f64> = outcomes.iter().map(|outcome| referee::points([outcome.a_score, outcome.b_score]) as f64).collect();
+    let margins: Vec<f64> = outcomes.iter().map(|outcome| (outcome.a_score - outcome.b_score) as f64).collect();
+
+    let (win_rate, win_rate_error) = mean_with_error(&points);
+    let (margin, margin_error) = mean_with_error(&margins);
+
+    println!("Games: {}", outcomes.len());
+    println!("A wins: {}, losses: {}, draws: {}", wins, losses, draws);
+    println!("A win rate: {:.1}% ± {:.1}% (95% CI)", win_rate * 100.0, win_rate_error * 100.0);
+    println!("A average margin: {:.2} ± {:.2} (95% CI)", margin, margin_error);
+}
+
+/// The sample mean and the half-width of its 95% confidence interval.
+fn mean_with_error(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+
+    if samples.len() < 2 {
+        return (mean, 0.0);
+    }
+
+    let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, 1.96 * (variance / n).sqrt())
+}
@@ -0,0 +1,204 @@
+//! Search benchmark: replays recorded matches with a node budget instead of a time budget.
+//!
+//! Every turn of every log is a position. What the bot remembers between turns comes from the
+//! recorded inputs, not from the actions it picks, so a changed decision leaves the later positions
+//! as they were. With a fixed node budget the search expands the same
+//! tree whatever the machine, so the depth and action of each position only change with the
+//! search itself and nodes/sec measures how fast it got there. `--save` writes the results to a
+//! baseline file, `--baseline` compares against one.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::process;
+use std::time::Duration;
+
+use cg_fall_2020::protocol::parse_turn;
+use cg_fall_2020::replay::read_log;
+use cg_fall_2020::{Bot, BotConfig, DEFAULT_NODE_BUDGET};
+
+const USAGE: &str = "usage: bench [--nodes N] [--set key=value]... [--baseline <file>] [--save <file>] <log>...
+
+Logs are recordings made with --record, bench/ holds the default corpus. The bot starts from the
+default settings with verbose=false and no time limit, and stops every search after N nodes,
+20000 by default.";
+
+struct Options {
+    config: BotConfig,
+    logs: Vec<String>,
+    baseline: Option<String>,
+    save: Option<String>,
+}
+
+/// The search of one position.
+struct Sample {
+    /// `<log>:<turn>`
+    position: String,
+    nodes: usize,
+    depth: i32,
+    elapsed: Duration,
+    action: String,
+}
+
+impl Sample {
+    fn nodes_per_sec(&self) -> f64 {
+        self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// A line of a baseline file: position, nodes, depth, nodes/sec and the action.
+struct BaselineSample {
+    depth: i32,
+    nodes_per_sec: f64,
+    action: String,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = run(&options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        config: BotConfig { verbose: false, ..BotConfig::default() }.with_node_budget(DEFAULT_NODE_BUDGET),
+        logs: Vec::new(),
+        baseline: None,
+        save: None,
+    };
+
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.logs.push(arg);
+            continue;
+        }
+
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+
+        match &arg[..] {
+            "--nodes" => options.config.node_limit = value.parse().map_err(|_| format!("invalid node count: {}", value))?,
+            "--set" => options.config.set_pair(&value)?,
+            "--baseline" => options.baseline = Some(value),
+            "--save" => options.save = Some(value),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    if options.logs.is_empty() {
+        return Err("no logs given".to_string());
+    }
+
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut samples = Vec::new();
+
+    for log in &options.logs {
+        let turns = read_log(BufReader::new(File::open(log)?))?;
+        let mut bot = Bot::with_config(options.config.clone());
+
+        for (i, turn) in turns.iter().enumerate() {
+            let decision = bot.play(parse_turn(&turn.input)?);
+            let stats = bot.last_search();
+
+            samples.push(Sample {
+                position: format!("{}:{}", log, i + 1),
+                nodes: stats.nodes,
+                depth: stats.depth,
+                elapsed: stats.elapsed,
+                action: decision.action.to_string(),
+            });
+        }
+    }
+
+    let baseline = match &options.baseline {
+        Some(path) => Some(read_baseline(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+
+    report(&samples, baseline.as_ref());
+
+    if let Some(path) = &options.save {
+        fs::write(path, to_baseline(&samples))?;
+    }
+
+    Ok(())
+}
+
+fn report(samples: &[Sample], baseline: Option<&HashMap<String, BaselineSample>>) {
+    let mut speedups = Vec::new();
+    let mut changed = 0;
+
+    for sample in samples {
+        print!("{:<32} {:>8} nodes  depth {:>2}  {:>10.0} nodes/s  {}", sample.position, sample.nodes, sample.depth, sample.nodes_per_sec(), sample.action);
+
+        if let Some(old) = baseline.and_then(|baseline| baseline.get(&sample.position)) {
+            speedups.push(sample.nodes_per_sec() / old.nodes_per_sec);
+
+            if old.depth != sample.depth || old.action != sample.action {
+                changed += 1;
+                print!("  (was depth {}, {})", old.depth, old.action);
+            }
+        }
+
+        println!();
+    }
+
+    let nodes: usize = samples.iter().map(|sample| sample.nodes).sum();
+    let elapsed: Duration = samples.iter().map(|sample| sample.elapsed).sum();
+    let depth = samples.iter().map(|sample| sample.depth as f64).sum::<f64>() / samples.len().max(1) as f64;
+
+    println!();
+    println!("Positions: {}", samples.len());
+    println!("Nodes: {} in {:.2}s, {:.0} nodes/s", nodes, elapsed.as_secs_f64(), nodes as f64 / elapsed.as_secs_f64().max(1e-9));
+    println!("Average depth: {:.2}", depth);
+
+    if baseline.is_some() {
+        // The geometric mean, so a few tiny positions don't dominate.
+        let speedup = (speedups.iter().map(|speedup| speedup.ln()).sum::<f64>() / speedups.len().max(1) as f64).exp();
+
+        println!("Against the baseline: {} positions compared, {} changed depth or action", speedups.len(), changed);
+        println!("Nodes/s: {:.2}x", speedup);
+    }
+}
+
+fn to_baseline(samples: &[Sample]) -> String {
+    samples.iter()
+        .map(|sample| format!("{} {} {} {:.0} {}\n", sample.position, sample.nodes, sample.depth, sample.nodes_per_sec(), sample.action))
+        .collect()
+}
+
+fn read_baseline(text: &str) -> Result<HashMap<String, BaselineSample>, String> {
+    let mut baseline = HashMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let invalid = || format!("invalid baseline line {}: {}", i + 1, line);
+        let mut fields = line.splitn(5, ' ');
+        let position = fields.next().ok_or_else(invalid)?;
+        let _nodes = fields.next().ok_or_else(invalid)?;
+        let depth = fields.next().and_then(|depth| depth.parse().ok()).ok_or_else(invalid)?;
+        let nodes_per_sec = fields.next().and_then(|rate| rate.parse().ok()).ok_or_else(invalid)?;
+        let action = fields.next().ok_or_else(invalid)?;
+
+        baseline.insert(position.to_string(), BaselineSample {
+            depth,
+            nodes_per_sec,
+            action: action.to_string(),
+        });
+    }
+
+    Ok(baseline)
+}
@@ -0,0 +1,74 @@
+//! Bundles the bot into the single source file the arena accepts.
+//!
+//! The library goes into a `cg_fall_2020` module with every bot module inlined, so `main.rs` can
+//! follow unchanged. The local tooling modules (`referee`, `tuner`) are left out, the bot never
+//! uses them. The bundle still needs the `rand` crate, which the arena provides:
+//!
+//! ```text
+//! cargo run --bin bundle -- submission.rs
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "usage: bundle [<out>]
+
+Writes the bot as one source file to <out>, target/submission.rs by default.";
+
+/// Modules only the local tools need.
+const TOOLING: [&str; 2] = ["referee", "tuner"];
+const LIBRARY: &str = "cg_fall_2020";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.len() > 1 || args.iter().any(|arg| arg.starts_with("--")) {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+
+    let out = args.first().map_or("target/submission.rs", |out| &out[..]);
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+
+    let result = bundle(&src).and_then(|bundle| fs::write(out, bundle).map_err(|error| format!("cannot write {}: {}", out, error)));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn bundle(src: &Path) -> Result<String, String> {
+    let read = |name: &str| fs::read_to_string(src.join(name)).map_err(|error| format!("cannot read src/{}: {}", name, error));
+
+    let mut library = String::new();
+
+    for line in read("lib.rs")?.lines() {
+        let module = line.strip_prefix("pub mod ").and_then(|rest| rest.strip_suffix(';'));
+
+        match module {
+            Some(module) if TOOLING.contains(&module) => {}
+            Some(module) => {
+                library.push_str(&format!("pub mod {} {{\n", module));
+                library.push_str(&read(&format!("{}.rs", module))?);
+                library.push_str("}\n");
+            }
+            None => {
+                library.push_str(line);
+                library.push('\n');
+            }
+        }
+    }
+
+    // The library's crate root is now a module of the bundle.
+    let library = library.replace("crate::", &format!("crate::{}::", LIBRARY));
+
+    Ok(format!(
+        "// Generated by `cargo run --bin bundle`, edit the sources under src/ instead.\n#![allow(dead_code)]\n\npub mod {} {{\n{}}}\n\n{}",
+        LIBRARY,
+        library,
+        read("main.rs")?
+    ))
+}
@@ -0,0 +1,72 @@
+//! Tunes the evaluation weights by evolving them against the default bot in self-play.
+//!
+//! Prints the best weights found in the `key=value` format the bot loads with `--params`.
+
+use std::env;
+use std::process;
+use std::thread;
+
+use cg_fall_2020::params::EvalParams;
+use cg_fall_2020::tuner::{self, TunerConfig};
+use cg_fall_2020::BotConfig;
+
+const USAGE: &str = "usage: tuner [--population N] [--generations N] [--games N] [--sigma X] [--seed S] [--threads T]
+             [--checkpoint <file>] [--set key=value]...
+
+Candidates and the baseline share the default bot settings with verbose=false and no time limit,
+stopping every search after 5000 nodes; --set changes them for both. An existing checkpoint is resumed.";
+
+fn main() {
+    let config = match parse_options(env::args().skip(1).collect()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(1);
+        }
+    };
+
+    match tuner::tune(&config) {
+        Ok(population) => match population.best() {
+            Some(best) => print!("{}", EvalParams::from_vec(&best.weights)),
+            None => eprintln!("No candidate was evaluated."),
+        },
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<TunerConfig, String> {
+    let mut config = TunerConfig {
+        population: 16,
+        generations: 20,
+        games: 40,
+        sigma: 0.2,
+        seed: 0,
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        checkpoint: "tuner.checkpoint".to_string(),
+        bot: BotConfig::self_play(),
+    };
+
+    let mut args = args.into_iter();
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        let invalid = || format!("invalid value for {}: {}", flag, value);
+
+        match &flag[..] {
+            "--population" => config.population = value.parse().map_err(|_| invalid())?,
+            "--generations" => config.generations = value.parse().map_err(|_| invalid())?,
+            "--games" => config.games = value.parse().map_err(|_| invalid())?,
+            "--sigma" => config.sigma = value.parse().map_err(|_| invalid())?,
+            "--seed" => config.seed = value.parse().map_err(|_| invalid())?,
+            "--threads" => config.threads = value.parse().map_err(|_| invalid())?,
+            "--checkpoint" => config.checkpoint = value.clone(),
+            "--set" => config.bot.set_pair(&value)?,
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+
+    Ok(config)
+}
@@ -0,0 +1,171 @@
+//! Every potion recipe and tome spell in the game.
+//!
+//! Ids match the ones the arena uses: potions are 42-77 and tome spells 0-41, both in deck order.
+//! Potion deltas are stored as costs (negative), the same way BREW lines report them.
+
+use std::collections::HashSet;
+
+use crate::{Potion, Spell};
+
+#[derive(Debug, PartialEq)]
+pub struct PotionRecipe {
+    pub id: i32,
+    pub delta: [i32; 4],
+    pub price: i32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TomeSpell {
+    pub id: i32,
+    pub delta: [i32; 4],
+    pub repeatable: bool,
+}
+
+macro_rules! potion {
+    ($id:expr, $d0:expr, $d1:expr, $d2:expr, $d3:expr, $price:expr) => {
+        PotionRecipe { id: $id, delta: [-$d0, -$d1, -$d2, -$d3], price: $price }
+    };
+}
+
+macro_rules! tome_spell {
+    ($id:expr, $d0:expr, $d1:expr, $d2:expr, $d3:expr, $repeatable:expr) => {
+        TomeSpell { id: $id, delta: [$d0, $d1, $d2, $d3], repeatable: $repeatable }
+    };
+}
+
+pub const POTIONS: [PotionRecipe; 36] = [
+    potion!(42, 2, 2, 0, 0, 6),
+    potion!(43, 3, 2, 0, 0, 7),
+    potion!(44, 0, 4, 0, 0, 8),
+    potion!(45, 2, 0, 2, 0, 8),
+    potion!(46, 2, 3, 0, 0, 8),
+    potion!(47, 3, 0, 2, 0, 9),
+    potion!(48, 0, 2, 2, 0, 10),
+    potion!(49, 0, 5, 0, 0, 10),
+    potion!(50, 2, 0, 0, 2, 10),
+    potion!(51, 2, 0, 3, 0, 11),
+    potion!(52, 3, 0, 0, 2, 11),
+    potion!(53, 0, 0, 4, 0, 12),
+    potion!(54, 0, 2, 0, 2, 12),
+    potion!(55, 0, 3, 2, 0, 12),
+    potion!(56, 0, 2, 3, 0, 13),
+    potion!(57, 0, 0, 2, 2, 14),
+    potion!(58, 0, 3, 0, 2, 14),
+    potion!(59, 2, 0, 0, 3, 14),
+    potion!(60, 0, 0, 5, 0, 15),
+    potion!(61, 0, 0, 0, 4, 16),
+    potion!(62, 0, 2, 0, 3, 16),
+    potion!(63, 0, 0, 3, 2, 17),
+    potion!(64, 0, 0, 2, 3, 18),
+    potion!(65, 0, 0, 0, 5, 20),
+    potion!(66, 2, 1, 0, 1, 9),
+    potion!(67, 0, 2, 1, 1, 12),
+    potion!(68, 1, 0, 2, 1, 12),
+    potion!(69, 2, 2, 2, 0, 13),
+    potion!(70, 2, 2, 0, 2, 15),
+    potion!(71, 2, 0, 2, 2, 17),
+    potion!(72, 0, 2, 2, 2, 19),
+    potion!(73, 1, 1, 1, 1, 12),
+    potion!(74, 3, 1, 1, 1, 14),
+    potion!(75, 1, 3, 1, 1, 16),
+    potion!(76, 1, 1, 3, 1, 18),
+    potion!(77, 1, 1, 1, 3, 20),
+];
+
+pub const TOME_SPELLS: [TomeSpell; 42] = [
+    tome_spell!(0, -3, 0, 0, 1, true),
+    tome_spell!(1, 3, -1, 0, 0, true),
+    tome_spell!(2, 1, 1, 0, 0, false),
+    tome_spell!(3, 0, 0, 1, 0, false),
+    tome_spell!(4, 3, 0, 0, 0, false),
+    tome_spell!(5, 2, 3, -2, 0, true),
+    tome_spell!(6, 2, 1, -2, 1, true),
+    tome_spell!(7, 3, 0, 1, -1, true),
+    tome_spell!(8, 3, -2, 1, 0, true),
+    tome_spell!(9, 2, -3, 2, 0, true),
+    tome_spell!(10, 2, 2, 0, -1, true),
+    tome_spell!(11, -4, 0, 2, 0, true),
+    tome_spell!(12, 2, 1, 0, 0, false),
+    tome_spell!(13, 4, 0, 0, 0, false),
+    tome_spell!(14, 0, 0, 0, 1, false),
+    tome_spell!(15, 0, 2, 0, 0, false),
+    tome_spell!(16, 1, 0, 1, 0, false),
+    tome_spell!(17, -2, 0, 1, 0, true),
+    tome_spell!(18, -1, -1, 0, 1, true),
+    tome_spell!(19, 0, 2, -1, 0, true),
+    tome_spell!(20, 2, -2, 0, 1, true),
+    tome_spell!(21, -3, 1, 1, 0, true),
+    tome_spell!(22, 0, 2, -2, 1, true),
+    tome_spell!(23, 1, -3, 1, 1, true),
+    tome_spell!(24, 0, 3, 0, -1, true),
+    tome_spell!(25, 0, -3, 0, 2, true),
+    tome_spell!(26, 1, 1, 1, -1, true),
+    tome_spell!(27, 1, 2, -1, 0, true),
+    tome_spell!(28, 4, 1, -1, 0, true),
+    tome_spell!(29, -5, 0, 0, 2, true),
+    tome_spell!(30, -4, 0, 1, 1, true),
+    tome_spell!(31, 0, 3, 2, -2, true),
+    tome_spell!(32, 1, 1, 3, -2, true),
+    tome_spell!(33, -5, 0, 3, 0, true),
+    tome_spell!(34, -2, 0, -1, 2, true),
+    tome_spell!(35, 0, 0, -3, 3, true),
+    tome_spell!(36, 0, -3, 3, 0, true),
+    tome_spell!(37, -3, 3, 0, 0, true),
+    tome_spell!(38, -2, 2, 0, 0, true),
+    tome_spell!(39, 0, 0, -2, 2, true),
+    tome_spell!(40, 0, -2, 2, 0, true),
+    tome_spell!(41, 0, 0, 2, -1, true),
+];
+
+pub fn find_potion(delta: &[i32; 4]) -> Option<&'static PotionRecipe> {
+    POTIONS.iter().find(|recipe| recipe.delta == *delta)
+}
+
+pub fn find_tome_spell(delta: &[i32; 4]) -> Option<&'static TomeSpell> {
+    TOME_SPELLS.iter().find(|spell| spell.delta == *delta)
+}
+
+/// Recipes that have not been seen on the board yet, given the catalog ids seen so far.
+pub fn unseen_potions(seen: &HashSet<i32>) -> Vec<&'static PotionRecipe> {
+    POTIONS.iter().filter(|recipe| !seen.contains(&recipe.id)).collect()
+}
+
+/// Tome spells that have not been seen in the tome yet, given the catalog ids seen so far.
+pub fn unseen_tome_spells(seen: &HashSet<i32>) -> Vec<&'static TomeSpell> {
+    TOME_SPELLS.iter().filter(|spell| !seen.contains(&spell.id)).collect()
+}
+
+/// The full potion deck, unshuffled.
+pub fn potion_deck() -> Vec<Potion> {
+    POTIONS.iter().map(|recipe| Potion {
+        id: recipe.id,
+        delta: recipe.delta,
+        price: recipe.price,
+        urgency_bonus: 0,
+        urgency_bonus_count: 0,
+    }).collect()
+}
+
+/// The full tome deck, unshuffled.
+pub fn tome_deck() -> Vec<Spell> {
+    TOME_SPELLS.iter().map(|spell| Spell {
+        id: spell.id,
+        delta: spell.delta,
+        read_ahead_tax: 0,
+        tax_count: 0,
+        castable: false,
+        repeatable: spell.repeatable,
+    }).collect()
+}
+
+impl Potion {
+    pub fn recipe(&self) -> Option<&'static PotionRecipe> {
+        find_potion(&self.delta)
+    }
+}
+
+impl Spell {
+    pub fn tome_spell(&self) -> Option<&'static TomeSpell> {
+        find_tome_spell(&self.delta)
+    }
+}
@@ -0,0 +1,156 @@
+//! Ingredients packed into one integer.
+//!
+//! Each tier takes one byte, so paying for a spell or a potion is a couple of integer operations
+//! instead of a loop over four tiers.
+
+use crate::MAX_INGREDIENTS;
+
+/// The top bit of every byte. Set before subtracting, it absorbs the borrow of a tier that goes
+/// negative, which is how an unaffordable cost is detected.
+const GUARD: u32 = 0x8080_8080;
+/// Multiplying by this sums the four bytes into the top one.
+const BYTE_SUM: u32 = 0x0101_0101;
+
+/// Tier `i` is stored in byte `i`. Counts never come close to the guard bit since the inventory
+/// holds at most 10 ingredients.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Ingredients(u32);
+
+impl Ingredients {
+    pub fn pack(ingredients: &[i32; 4]) -> Ingredients {
+        Ingredients(pack_bytes(ingredients))
+    }
+
+    pub fn unpack(self) -> [i32; 4] {
+        [self.tier(0), self.tier(1), self.tier(2), self.tier(3)]
+    }
+
+    pub fn tier(self, tier: usize) -> i32 {
+        (self.0 >> (8 * tier) & 0xff) as i32
+    }
+
+    pub fn total(self) -> i32 {
+        (self.0.wrapping_mul(BYTE_SUM) >> 24) as i32
+    }
+
+    /// Adds tier-0 ingredients, as many as still fit in the inventory.
+    pub fn add_tier_0(self, count: i32) -> Ingredients {
+        Ingredients(self.0 + count.min(MAX_INGREDIENTS - self.total()).max(0) as u32)
+    }
+
+    /// The inventory left after paying `delta`, or `None` if a tier runs short or the inventory
+    /// overflows.
+    pub fn pay(self, delta: Delta) -> Option<Ingredients> {
+        let paid = (self.0 | GUARD) - delta.cost;
+
+        if paid & GUARD != GUARD {
+            return None;
+        }
+
+        let result = Ingredients((paid & !GUARD) + delta.gain);
+
+        match result.total() > MAX_INGREDIENTS {
+            true => None,
+            false => Some(result),
+        }
+    }
+}
+
+/// A spell or potion delta split into what it consumes and what it produces, one byte per tier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Delta {
+    cost: u32,
+    gain: u32,
+}
+
+impl Delta {
+    pub fn pack(delta: &[i32; 4]) -> Delta {
+        Delta {
+            cost: pack_bytes(&[(-delta[0]).max(0), (-delta[1]).max(0), (-delta[2]).max(0), (-delta[3]).max(0)]),
+            gain: pack_bytes(&[delta[0].max(0), delta[1].max(0), delta[2].max(0), delta[3].max(0)]),
+        }
+    }
+
+    /// The delta of casting a spell `times` times in one go.
+    pub fn times(self, times: i32) -> Delta {
+        Delta {
+            cost: self.cost * times as u32,
+            gain: self.gain * times as u32,
+        }
+    }
+}
+
+fn pack_bytes(values: &[i32; 4]) -> u32 {
+    values.iter().enumerate().fold(0, |packed, (i, value)| packed | (*value as u32 & 0xff) << (8 * i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{POTIONS, TOME_SPELLS};
+
+    const STARTING_SPELLS: [[i32; 4]; 4] = [[2, 0, 0, 0], [-1, 1, 0, 0], [0, -1, 1, 0], [0, 0, -1, 1]];
+
+    /// Tier by tier, the way it was done before the ingredients were packed.
+    fn pay(ingredients: &[i32; 4], delta: &[i32; 4], times: i32) -> Option<[i32; 4]> {
+        let mut result = *ingredients;
+
+        for tier in 0..4 {
+            result[tier] += delta[tier] * times;
+
+            if result[tier] < 0 {
+                return None;
+            }
+        }
+
+        match result.iter().sum::<i32>() > MAX_INGREDIENTS {
+            true => None,
+            false => Some(result),
+        }
+    }
+
+    /// Every inventory that fits, 1001 of them.
+    fn inventories() -> Vec<[i32; 4]> {
+        let mut inventories = Vec::new();
+
+        for a in 0..=MAX_INGREDIENTS {
+            for b in 0..=MAX_INGREDIENTS - a {
+                for c in 0..=MAX_INGREDIENTS - a - b {
+                    for d in 0..=MAX_INGREDIENTS - a - b - c {
+                        inventories.push([a, b, c, d]);
+                    }
+                }
+            }
+        }
+
+        inventories
+    }
+
+    #[test]
+    fn pay_matches_paying_tier_by_tier() {
+        let deltas: Vec<[i32; 4]> = POTIONS.iter().map(|potion| potion.delta)
+            .chain(TOME_SPELLS.iter().map(|spell| spell.delta))
+            .chain(STARTING_SPELLS.iter().copied())
+            .collect();
+        let inventories = inventories();
+
+        assert_eq!(inventories.len(), 1001);
+
+        for ingredients in &inventories {
+            let packed = Ingredients::pack(ingredients);
+
+            assert_eq!(packed.unpack(), *ingredients);
+            assert_eq!(packed.total(), ingredients.iter().sum::<i32>());
+
+            for delta in &deltas {
+                for times in 1..=4 {
+                    assert_eq!(
+                        packed.pay(Delta::pack(delta).times(times)).map(Ingredients::unpack),
+                        pay(ingredients, delta, times),
+                        "{:?} paying {:?} x{}", ingredients, delta, times
+                    );
+                }
+            }
+        }
+    }
+}
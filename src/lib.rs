@@ -0,0 +1,1650 @@
+use std::cell::RefCell;
+use std::cmp::{max, Ordering};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::Action::{Brew, Cast, Learn, Rest, Wait};
+use crate::catalog::{PotionRecipe, TomeSpell};
+use crate::inventory::{Delta, Ingredients};
+use crate::params::EvalParams;
+use crate::protocol::TurnReader;
+use crate::replay::Recorder;
+use crate::search_tree::{SearchTree, UNRECORDED};
+use crate::telemetry::SearchStats;
+use crate::timing::TimeManager;
+use crate::transitions::{CostMap, Transitions};
+
+pub mod catalog;
+pub mod inventory;
+pub mod mcts;
+pub mod opponent;
+pub mod params;
+pub mod protocol;
+pub mod referee;
+pub mod replay;
+pub mod search_tree;
+pub mod telemetry;
+pub mod timing;
+pub mod transitions;
+pub mod tuner;
+
+pub const MAX_TURNS: i32 = 100;
+pub const MAX_BREWS: i32 = 6;
+pub const MAX_INGREDIENTS: i32 = 10;
+/// Nodes per search when searches have to be reproducible, see `BotConfig::with_node_budget`.
+pub const DEFAULT_NODE_BUDGET: usize = 20000;
+/// Nodes per search in local self-play, about what a 5 ms search gets through.
+pub const SELF_PLAY_NODE_BUDGET: usize = 5000;
+
+/// Winning lines always beat non-terminal ones and losing lines always lose to them.
+const ENDGAME_WIN: f32 = 1_000_000.0;
+/// Potions further away than this many turns are not worth anything to the evaluator yet.
+const BREW_HORIZON: u8 = 5;
+
+/// Plays a match on stdin/stdout, optionally recording every turn for later replay.
+pub fn run(config: BotConfig, mut recorder: Option<Recorder>) {
+    let stdin = io::stdin();
+    let mut reader = TurnReader::new(stdin.lock());
+    let mut bot = Bot::with_config(config);
+
+    // game loop
+    loop {
+        match reader.read_turn() {
+            Ok(Some(game)) => {
+                let decision = bot.play(game);
+                println!("{}", decision);
+
+                if let Some(recorder) = recorder.as_mut() {
+                    if let Err(error) = recorder.record(reader.last_input(), &decision.action) {
+                        eprintln!("Recording failed: {}", error);
+                    }
+                }
+            }
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("Stopping: {}", error);
+                break;
+            }
+        }
+    }
+}
+
+/// What the bot remembers between turns of a match.
+pub struct Bot {
+    config: BotConfig,
+    my_prev_score: i32,
+    opp_prev_score: i32,
+    opp_brew_count: i32,
+    my_brew_count: i32,
+    turn_count: i32,
+    seen_potions: HashSet<i32>,
+    seen_tome_spells: HashSet<i32>,
+    /// Built for the current spellbook, so only after turn 1 and after learning.
+    transitions: Option<Arc<Transitions>>,
+    /// Cost maps of the potions seen since `transitions` was built.
+    cost_maps: HashMap<i32, Arc<CostMap>>,
+    last_search: SearchStats,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Bot::new()
+    }
+}
+
+impl Bot {
+    pub fn new() -> Bot {
+        Bot::with_config(BotConfig::default())
+    }
+
+    pub fn with_config(config: BotConfig) -> Bot {
+        Bot {
+            config,
+            my_prev_score: 0,
+            opp_prev_score: 0,
+            opp_brew_count: 0,
+            my_brew_count: 0,
+            turn_count: 1,
+            seen_potions: HashSet::new(),
+            seen_tome_spells: HashSet::new(),
+            transitions: None,
+            cost_maps: HashMap::new(),
+            last_search: SearchStats::default(),
+        }
+    }
+
+    /// Potion recipes that have not shown up on the board so far this match.
+    pub fn potions_in_deck(&self) -> Vec<&'static PotionRecipe> {
+        catalog::unseen_potions(&self.seen_potions)
+    }
+
+    /// Tome spells that have not shown up in the tome so far this match.
+    pub fn tome_spells_in_deck(&self) -> Vec<&'static TomeSpell> {
+        catalog::unseen_tome_spells(&self.seen_tome_spells)
+    }
+
+    /// Carries on from `turn`, for positions that don't start at the beginning of a match.
+    pub fn set_turn(&mut self, turn: i32) {
+        self.turn_count = turn;
+    }
+
+    /// Picks the action for a freshly parsed turn.
+    pub fn play(&mut self, mut game: GameState) -> Decision {
+        self.observe(&mut game);
+
+        if self.config.verbose {
+            eprintln!("My brew: {}. Opp brew: {}. Turn: {}", self.my_brew_count, self.opp_brew_count, self.turn_count);
+        }
+
+        let (decision, stats) = search(&game, &self.config);
+
+        self.turn_count += 1;
+        self.last_search = stats;
+        decision
+    }
+
+    /// What the search of the last `play` reported about itself.
+    pub fn last_search(&self) -> &SearchStats {
+        &self.last_search
+    }
+
+    /// Fills in the parts of `game` that come from earlier turns rather than from the input.
+    pub fn observe(&mut self, game: &mut GameState) {
+        // Brewing is the only way to earn rupees, so the input tells who brewed whatever we played.
+        if self.my_prev_score != game.my_rupees {
+            self.my_brew_count += 1;
+            self.my_prev_score = game.my_rupees;
+        }
+
+        if self.opp_prev_score != game.opp_rupees {
+            self.opp_brew_count += 1;
+            self.opp_prev_score = game.opp_rupees;
+        }
+
+        for recipe in game.potions.iter().filter_map(|potion| potion.recipe()) {
+            self.seen_potions.insert(recipe.id);
+        }
+
+        for spell in game.tome_spells.iter().filter_map(|spell| spell.tome_spell()) {
+            self.seen_tome_spells.insert(spell.id);
+        }
+
+        game.my_brew_count = self.my_brew_count;
+        game.opp_brew_count = self.opp_brew_count;
+        game.turn_count = self.turn_count;
+        game.opp_brew_turns = opponent::turns_to_brew(game);
+
+        let transitions = match self.transitions.take() {
+            Some(transitions) if transitions.matches(&game.my_cast) => transitions,
+            _ => {
+                self.cost_maps.clear();
+                Arc::new(Transitions::build(&game.my_cast))
+            }
+        };
+
+        for potion in &game.potions {
+            let map = self.cost_maps.entry(potion.id).or_insert_with(|| Arc::new(transitions.cost_map(&potion.delta)));
+            game.casts_to_brew.insert(potion.id, Arc::clone(map));
+        }
+
+        game.transitions = Some(Arc::clone(&transitions));
+        self.transitions = Some(transitions);
+        game.endgame = is_endgame(game, self.config.horizon());
+    }
+}
+
+/// Settings that change how the bot plays, so variants can be compared locally.
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    /// Time per turn after the first, in milliseconds from the arrival of the turn input.
+    pub time_limit: u64,
+    /// Time for the first turn, in milliseconds from the arrival of the turn input.
+    pub first_turn_time_limit: u64,
+    /// The search stops expanding below this depth.
+    pub max_depth: i32,
+    /// The search stops after generating this many nodes, like it does when time runs out. 0 for
+    /// no limit.
+    pub node_limit: usize,
+    /// Log each turn's search to stderr.
+    pub verbose: bool,
+    /// End each turn with a JSON line on stderr, see `telemetry`.
+    pub telemetry: bool,
+    /// Write the search tree of this turn to a DOT file, see `search_tree`. 0 for none.
+    pub dot_turn: i32,
+    /// The most nodes written to the DOT file.
+    pub dot_nodes: usize,
+    /// Skip states the search has already reached, see `TranspositionTable`.
+    pub transpositions: bool,
+    pub strategy: Strategy,
+    pub evaluator: Evaluator,
+    /// States kept per depth by the beam search.
+    pub beam_width: usize,
+    /// The beam search stops below this depth.
+    pub beam_depth: i32,
+    /// Depth at which MCTS stops expanding and rollouts stop playing.
+    pub mcts_horizon: i32,
+    /// UCT exploration constant, relative to the range of rollout rewards seen during the search.
+    pub mcts_exploration: f32,
+    pub params: EvalParams,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            time_limit: 41,
+            first_turn_time_limit: 950,
+            max_depth: 4,
+            node_limit: 0,
+            verbose: true,
+            telemetry: false,
+            dot_turn: 0,
+            dot_nodes: 2000,
+            transpositions: true,
+            strategy: Strategy::BreadthFirst,
+            evaluator: Evaluator::Default,
+            beam_width: 100,
+            beam_depth: 20,
+            mcts_horizon: 12,
+            mcts_exploration: 1.4,
+            params: EvalParams::default(),
+        }
+    }
+}
+
+impl BotConfig {
+    /// The default settings with the small node budget and quiet logs of local self-play. Searches
+    /// don't depend on the clock, so a seed always plays out the same match.
+    pub fn self_play() -> BotConfig {
+        BotConfig { verbose: false, ..BotConfig::default() }.with_node_budget(SELF_PLAY_NODE_BUDGET)
+    }
+
+    /// Stops every search after `nodes` nodes instead of on the clock, so a position always gets
+    /// the same search however fast or busy the machine is.
+    pub fn with_node_budget(self, nodes: usize) -> BotConfig {
+        BotConfig {
+            time_limit: u32::MAX as u64,
+            first_turn_time_limit: u32::MAX as u64,
+            node_limit: nodes,
+            ..self
+        }
+    }
+
+    /// How many turns ahead the active strategy looks.
+    pub fn horizon(&self) -> i32 {
+        match self.strategy {
+            Strategy::BreadthFirst => self.max_depth,
+            Strategy::Beam => self.beam_depth,
+            Strategy::MonteCarlo => self.mcts_horizon,
+            Strategy::BestFirst => self.max_depth,
+        }
+    }
+
+    /// Sets one setting from its `key=value` form, as passed to `--set`.
+    pub fn set_pair(&mut self, pair: &str) -> Result<(), String> {
+        let (key, value) = pair.split_once('=').ok_or_else(|| format!("expected key=value, got {}", pair))?;
+        self.set(key, value)
+    }
+
+    /// Sets one setting from its `key=value` form. Evaluation weights are accepted too.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "time_limit" => self.time_limit = parse_setting(key, value)?,
+            "first_turn_time_limit" => self.first_turn_time_limit = parse_setting(key, value)?,
+            "max_depth" => self.max_depth = parse_setting(key, value)?,
+            "node_limit" => self.node_limit = parse_setting(key, value)?,
+            "verbose" => self.verbose = parse_setting(key, value)?,
+            "telemetry" => self.telemetry = parse_setting(key, value)?,
+            "dot_turn" => self.dot_turn = parse_setting(key, value)?,
+            "dot_nodes" => self.dot_nodes = parse_setting(key, value)?,
+            "transpositions" => self.transpositions = parse_setting(key, value)?,
+            "strategy" => self.strategy = parse_setting(key, value)?,
+            "evaluator" => self.evaluator = parse_setting(key, value)?,
+            "beam_width" => self.beam_width = parse_setting(key, value)?,
+            "beam_depth" => self.beam_depth = parse_setting(key, value)?,
+            "mcts_horizon" => self.mcts_horizon = parse_setting(key, value)?,
+            "mcts_exploration" => self.mcts_exploration = parse_setting(key, value)?,
+            _ => return self.params.set(key, value),
+        }
+
+        Ok(())
+    }
+}
+
+/// How `get_best_action` searches.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Strategy {
+    /// Expands every state up to `max_depth`.
+    BreadthFirst,
+    /// Keeps the `beam_width` best states of each depth, down to `beam_depth`.
+    Beam,
+    /// Monte Carlo tree search, see `mcts`.
+    MonteCarlo,
+    /// Always expands the best state found so far, down to `max_depth`.
+    BestFirst,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Strategy, String> {
+        match text {
+            "bfs" => Ok(Strategy::BreadthFirst),
+            "beam" => Ok(Strategy::Beam),
+            "mcts" => Ok(Strategy::MonteCarlo),
+            "best" => Ok(Strategy::BestFirst),
+            _ => Err(format!("unknown strategy: {}", text)),
+        }
+    }
+}
+
+/// Which `StateEvaluator` the search ranks states with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Evaluator {
+    /// `DefaultStateEvaluator`, weighted by `params`.
+    Default,
+    /// `RupeeStateEvaluator`.
+    Rupees,
+}
+
+impl FromStr for Evaluator {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Evaluator, String> {
+        match text {
+            "default" => Ok(Evaluator::Default),
+            "rupees" => Ok(Evaluator::Rupees),
+            _ => Err(format!("unknown evaluator: {}", text)),
+        }
+    }
+}
+
+fn parse_setting<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("invalid value for {}: {}", key, value))
+}
+
+pub struct GameState {
+    pub my_rupees: i32,
+    pub opp_rupees: i32,
+    pub my_ingredients: [i32; 4],
+    pub opp_ingredients: [i32; 4],
+    pub potions: BinaryHeap<Potion>,
+    pub my_cast: Vec<Spell>,
+    pub opp_cast: Vec<Spell>,
+    pub tome_spells: Vec<Spell>,
+    pub my_disabled_spells: HashSet<i32>,
+    pub my_brew_count: i32,
+    pub opp_brew_count: i32,
+    pub turn_count: i32,
+    /// Turns the opponent needs to brew each potion they can reach, see `opponent::turns_to_brew`.
+    pub opp_brew_turns: HashMap<i32, i32>,
+    /// Set when the match can end inside the search horizon, see `is_endgame`.
+    pub endgame: bool,
+    /// When the first line of this turn's input arrived. The turn's time budget counts from here.
+    pub received_at: Instant,
+    /// Transitions through `my_cast`, filled in by `Bot::observe`.
+    pub transitions: Option<Arc<Transitions>>,
+    /// For each potion, `Transitions::cost_map` of its cost.
+    pub casts_to_brew: HashMap<i32, Arc<CostMap>>,
+    /// `turns_to_brew` results of this turn's search, by inventory and exhausted spells.
+    pub brew_turns: RefCell<BrewTurnsCache>,
+}
+
+/// Turns to brew each potion, in `potions.iter()` order. There are never more than 8 potions on
+/// the board, which `State::brewed_potions` relies on too.
+pub type BrewTurns = [Option<u8>; 8];
+/// `GameState::turns_to_brew` results, by inventory and exhausted spells.
+pub type BrewTurnsCache = HashMap<(Ingredients, u64), BrewTurns>;
+
+impl GameState {
+    /// The fewest casts from `ingredients` to affording `potion`, if it can be reached at all.
+    /// Only known once `Bot::observe` has built the transitions.
+    pub fn casts_to_brew(&self, potion: &Potion, ingredients: Ingredients) -> Option<i32> {
+        let transitions = self.transitions.as_ref()?;
+
+        match self.casts_to_brew.get(&potion.id)?.distances[transitions.index(ingredients)] {
+            transitions::UNREACHABLE => None,
+            casts => Some(casts as i32),
+        }
+    }
+
+    /// For each potion, the fewest turns until it is brewed from `ingredients`, the brew itself
+    /// included, or `None` past `BREW_HORIZON`. Spell `i` of
+    /// `my_cast` needs a REST first while bit `i` of `exhausted` is set.
+    pub fn turns_to_brew(&self, ingredients: Ingredients, exhausted: u64) -> BrewTurns {
+        let transitions = match &self.transitions {
+            Some(transitions) => transitions,
+            None => return [None; 8],
+        };
+
+        *self.brew_turns.borrow_mut().entry((ingredients, exhausted)).or_insert_with(|| {
+            let mut turns = [None; 8];
+
+            for (p, potion) in self.potions.iter().enumerate().take(8) {
+                if let Some(map) = self.casts_to_brew.get(&potion.id) {
+                    turns[p] = transitions.turns_to_afford(ingredients, exhausted, map, BREW_HORIZON - 1).map(|turns| turns + 1);
+                }
+            }
+
+            turns
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Potion {
+    pub id: i32,
+    pub delta: [i32; 4],
+    /// Includes the urgency bonus.
+    pub price: i32,
+    pub urgency_bonus: i32,
+    /// How many more times the urgency bonus can be gained.
+    pub urgency_bonus_count: i32,
+}
+
+impl Potion {
+    pub fn base_price(&self) -> i32 {
+        self.price - self.urgency_bonus
+    }
+
+    /// Whether the opponent can brew this potion on an earlier turn than `depth` turns from now,
+    /// in which case we count it as gone by then, urgency bonus and all.
+    fn is_taken_before(&self, depth: i32, game: &GameState) -> bool {
+        matches!(game.opp_brew_turns.get(&self.id), Some(turns) if *turns < depth)
+    }
+}
+
+impl Ord for Potion {
+    fn cmp(&self, other: &Potion) -> Ordering {
+        self.price.cmp(&other.price)
+    }
+}
+
+impl PartialOrd for Potion {
+    fn partial_cmp(&self, other: &Potion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spell {
+    pub id: i32,
+    pub delta: [i32; 4],
+    pub read_ahead_tax: i32,
+    pub tax_count: i32,
+    pub castable: bool,
+    pub repeatable: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    Wait,
+    Brew(i32),
+    Cast(i32, i32),
+    Learn(i32),
+    Rest,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Brew(id) => write!(f, "BREW {}", id),
+            Cast(id, times) => write!(f, "CAST {} {}", id, times),
+            Learn(id) => write!(f, "LEARN {}", id),
+            Wait => write!(f, "WAIT"),
+            Rest => write!(f, "REST"),
+        }
+    }
+}
+
+/// The action for a turn and the note shown next to it in the replay viewer.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Decision {
+    pub action: Action,
+    /// Why the search picked the action, kept short enough for the viewer's speech bubble.
+    pub message: Option<String>,
+}
+
+impl From<Action> for Decision {
+    fn from(action: Action) -> Self {
+        Decision { action, message: None }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{} {}", self.action, message),
+            None => write!(f, "{}", self.action),
+        }
+    }
+}
+
+/// A search node. Spells and potions are referred to by their index in this turn's `GameState`
+/// lists, so every set is a bitmask and the whole state is a plain copy.
+#[derive(Debug, Copy, Clone)]
+pub struct State {
+    /// Bit `i` is set when `game.tome_spells[i]` was learned.
+    new_spells: u8,
+    ingredients: Ingredients,
+    /// Bit `i` is set when `game.my_cast[i]` is exhausted.
+    disabled_spells: u64,
+    /// Bit `i` is set when the learned `game.tome_spells[i]` is exhausted.
+    disabled_new_spells: u8,
+    /// Bit `i` is set when the `i`th potion of `game.potions.iter()` was brewed.
+    brewed_potions: u8,
+    /// The tax pile on each of `game.tome_spells`, our own deposits included.
+    tome_tax: [u8; 8],
+    depth: i32,
+    root_action: Action,
+    cumulative_score: f32,
+    my_rupees: i32,
+}
+
+impl State {
+    /// Turns since the root.
+    pub fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    /// The first action of the line leading here.
+    pub fn root_action(&self) -> Action {
+        self.root_action
+    }
+
+    pub fn ingredients(&self) -> Ingredients {
+        self.ingredients
+    }
+
+    pub fn my_rupees(&self) -> i32 {
+        self.my_rupees
+    }
+
+    fn disabled_spell_count(&self) -> u32 {
+        self.disabled_spells.count_ones() + self.disabled_new_spells.count_ones()
+    }
+}
+
+/// Hashes what the position is made of, not how the search got there: `depth`, `root_action` and
+/// `cumulative_score` are left out.
+impl Hash for State {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.my_rupees.hash(hasher);
+        self.ingredients.hash(hasher);
+        self.new_spells.hash(hasher);
+        self.disabled_spells.hash(hasher);
+        self.disabled_new_spells.hash(hasher);
+        self.brewed_potions.hash(hasher);
+        self.tome_tax.hash(hasher);
+    }
+}
+
+/// Remembers the shallowest depth each position was reached at, so the search expands it once.
+/// CAST A then CAST B reaches the same position as CAST B then CAST A.
+struct TranspositionTable {
+    enabled: bool,
+    entries: HashMap<u64, (i32, f32)>,
+    /// How many duplicate states were dropped.
+    saved: usize,
+}
+
+impl TranspositionTable {
+    fn new(enabled: bool) -> TranspositionTable {
+        TranspositionTable {
+            enabled,
+            entries: HashMap::new(),
+            saved: 0,
+        }
+    }
+
+    /// Whether `state` is worth queueing. A duplicate is dropped when the position was already
+    /// reached at a shallower depth, or at the same depth through a line scoring at least as well.
+    /// A better duplicate replaces the queued copy, see `is_superseded`.
+    fn insert(&mut self, state: &State) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(&(depth, cumulative_score)) = self.entries.get(&key) {
+            if depth < state.depth || (depth == state.depth && cumulative_score >= state.cumulative_score) {
+                self.saved += 1;
+                return false;
+            }
+        }
+
+        self.entries.insert(key, (state.depth, state.cumulative_score));
+        true
+    }
+
+    /// Whether a better copy of the queued `state` was inserted since, so it shouldn't be expanded.
+    fn is_superseded(&mut self, state: &State) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+
+        let superseded = match self.entries.get(&hasher.finish()) {
+            Some(&(depth, cumulative_score)) => depth < state.depth || (depth == state.depth && cumulative_score > state.cumulative_score),
+            None => false,
+        };
+
+        if superseded {
+            self.saved += 1;
+        }
+
+        superseded
+    }
+}
+
+/// Values a search state, higher being better for us. States are only ever compared with other
+/// states of the same turn's search.
+pub trait StateEvaluator {
+    fn evaluate(&self, state: &State, game: &GameState) -> f32;
+}
+
+/// Everything `score` knows about: potions in reach, spells, the opponent and the endgame.
+pub struct DefaultStateEvaluator {
+    params: EvalParams,
+}
+
+impl DefaultStateEvaluator {
+    pub fn new(params: EvalParams) -> DefaultStateEvaluator {
+        DefaultStateEvaluator { params }
+    }
+}
+
+impl StateEvaluator for DefaultStateEvaluator {
+    fn evaluate(&self, state: &State, game: &GameState) -> f32 {
+        score(state, game, &self.params)
+    }
+}
+
+/// Just the rupees earned, a baseline that only sees potions the search reaches.
+pub struct RupeeStateEvaluator;
+
+impl StateEvaluator for RupeeStateEvaluator {
+    fn evaluate(&self, state: &State, _game: &GameState) -> f32 {
+        state.my_rupees as f32
+    }
+}
+
+/// A search algorithm, which picks this turn's action by exploring states from the root.
+pub trait SolutionFinder {
+    /// Searches until the deadline or `config`'s limits, filling in `stats` and `tree` on the way.
+    fn search(&self, game: &GameState, config: &BotConfig, deadline: &Instant, stats: &mut SearchStats, tree: &mut SearchTree) -> Decision;
+}
+
+/// The evaluator and search `config` asks for.
+pub fn solution_finder(config: &BotConfig) -> Box<dyn SolutionFinder> {
+    let evaluator: Box<dyn StateEvaluator> = match config.evaluator {
+        Evaluator::Default => Box::new(DefaultStateEvaluator::new(config.params.clone())),
+        Evaluator::Rupees => Box::new(RupeeStateEvaluator),
+    };
+
+    match config.strategy {
+        Strategy::BreadthFirst => Box::new(BreadthFirstSolutionFinder::new(evaluator)),
+        Strategy::Beam => Box::new(BeamSolutionFinder::new(evaluator)),
+        Strategy::MonteCarlo => Box::new(mcts::MonteCarloSolutionFinder::new(evaluator)),
+        Strategy::BestFirst => Box::new(BestFirstSolutionFinder::new(evaluator)),
+    }
+}
+
+pub fn get_best_action(game: &GameState, config: &BotConfig) -> Decision {
+    search(game, config).0
+}
+
+/// `get_best_action`, together with what the search reported about itself.
+pub fn search(game: &GameState, config: &BotConfig) -> (Decision, SearchStats) {
+    let started = Instant::now();
+    let deadline = TimeManager::new(config).deadline(game);
+    let mut stats = SearchStats::default();
+    let mut tree = match game.turn_count == config.dot_turn {
+        true => SearchTree::new(config.dot_nodes),
+        false => SearchTree::new(0),
+    };
+
+    let decision = solution_finder(config).search(game, config, &deadline, &mut stats, &mut tree);
+
+    stats.elapsed = started.elapsed();
+
+    if config.telemetry {
+        eprintln!("{}", stats.to_json(game));
+    }
+
+    if game.turn_count == config.dot_turn {
+        let path = format!("search-{}.dot", game.turn_count);
+
+        if let Err(error) = tree.write(&path) {
+            eprintln!("Writing {} failed: {}", path, error);
+        }
+    }
+
+    (decision, stats)
+}
+
+fn root_state(game: &GameState) -> State {
+    let mut disabled_spells = 0;
+    for (i, spell) in game.my_cast.iter().enumerate().take(64) {
+        if !spell.castable {
+            disabled_spells |= 1 << i;
+        }
+    }
+
+    let mut tome_tax = [0; 8];
+    for (i, spell) in game.tome_spells.iter().enumerate().take(8) {
+        tome_tax[i] = spell.tax_count.clamp(0, u8::MAX as i32) as u8;
+    }
+
+    State {
+        ingredients: Ingredients::pack(&game.my_ingredients),
+        new_spells: 0,
+        disabled_spells,
+        disabled_new_spells: 0,
+        depth: 0,
+        root_action: Action::Wait,
+        cumulative_score: 0.0,
+        my_rupees: game.my_rupees,
+        brewed_potions: 0,
+        tome_tax,
+    }
+}
+
+/// The bookkeeping every tree search does for the states it generates: dropping transpositions,
+/// scoring, adding them to the DOT tree and reporting them to telemetry. A search only decides
+/// which state to `expand` next and what to make of the children.
+struct Expander<'a> {
+    game: &'a GameState,
+    config: &'a BotConfig,
+    state_evaluator: &'a dyn StateEvaluator,
+    deadline: &'a Instant,
+    stats: &'a mut SearchStats,
+    tree: &'a mut SearchTree,
+    table: TranspositionTable,
+}
+
+impl<'a> Expander<'a> {
+    fn new(game: &'a GameState, config: &'a BotConfig, state_evaluator: &'a dyn StateEvaluator, deadline: &'a Instant, stats: &'a mut SearchStats, tree: &'a mut SearchTree) -> Expander<'a> {
+        Expander {
+            game,
+            config,
+            state_evaluator,
+            deadline,
+            stats,
+            tree,
+            table: TranspositionTable::new(config.transpositions),
+        }
+    }
+
+    /// The root state with its score and tree node.
+    fn root(&mut self) -> (State, f32, usize) {
+        let root = root_state(self.game);
+        let score = self.state_evaluator.evaluate(&root, self.game);
+        self.table.insert(&root);
+
+        (root, score, self.tree.add(UNRECORDED, Wait, 0, 0.0, score))
+    }
+
+    /// The children of `state`, scored and recorded under the node `id`, leaving out the
+    /// transpositions. Scores include the `cumulative_score`, as the searches rank them.
+    fn expand<'b>(&'b mut self, state: &State, score: f32, id: usize) -> impl Iterator<Item = (State, f32, usize)> + 'b {
+        let parent = *state;
+        let game: &'b GameState = self.game;
+        let state_evaluator: &'b dyn StateEvaluator = self.state_evaluator;
+        let telemetry = self.config.telemetry;
+        let stats: &'b mut SearchStats = self.stats;
+        let tree: &'b mut SearchTree = self.tree;
+        let table = &mut self.table;
+
+        get_children(&parent, game, score, self.deadline).into_iter().filter_map(move |child| {
+            if !table.insert(&child) {
+                return None;
+            }
+
+            let child_score = state_evaluator.evaluate(&child, game) + child.cumulative_score;
+            let child_id = record(tree, id, &parent, &child, child_score, game);
+
+            if telemetry {
+                stats.candidate(child.root_action, child_score);
+            }
+
+            Some((child, child_score, child_id))
+        })
+    }
+
+    /// Whether `state` was queued before a better copy of it turned up, see
+    /// `TranspositionTable::is_superseded`.
+    fn is_superseded(&mut self, state: &State) -> bool {
+        self.table.is_superseded(state)
+    }
+
+    /// How many duplicate states were dropped.
+    fn saved(&self) -> usize {
+        self.table.saved
+    }
+}
+
+/// Iterative deepening over whole BFS layers. The best root action only moves on once a layer is
+/// complete, so running out of time halfway through a layer doesn't favour the root actions that
+/// happened to be expanded first.
+pub struct BreadthFirstSolutionFinder {
+    state_evaluator: Box<dyn StateEvaluator>,
+}
+
+impl BreadthFirstSolutionFinder {
+    pub fn new(state_evaluator: Box<dyn StateEvaluator>) -> BreadthFirstSolutionFinder {
+        BreadthFirstSolutionFinder { state_evaluator }
+    }
+}
+
+impl SolutionFinder for BreadthFirstSolutionFinder {
+    fn search(&self, game: &GameState, config: &BotConfig, deadline: &Instant, stats: &mut SearchStats, tree: &mut SearchTree) -> Decision {
+        let mut expander = Expander::new(game, config, &*self.state_evaluator, deadline, stats, tree);
+        let (root, root_score, root_id) = expander.root();
+        let mut layer = vec![(root, root_score, root_id)];
+        let mut depth = 0;
+
+        let mut node_count = 1;
+        let mut max_width = 0;
+        let mut best = (root, f32::MIN, root_id);
+
+        while !layer.is_empty() && depth <= config.max_depth {
+            let mut next = Vec::new();
+            let mut layer_best = best;
+
+            for (state, parent_score, id) in &layer {
+                if out_of_budget(deadline, config, node_count + next.len()) {
+                    break;
+                }
+
+                if is_game_over(state, game) || expander.is_superseded(state) {
+                    continue;
+                }
+
+                let mut width = 0;
+                for (child, child_score, child_id) in expander.expand(state, *parent_score, *id) {
+                    if layer_best.1 < child_score {
+                        layer_best = (child, child_score, child_id);
+                    }
+
+                    width += 1;
+                    next.push((child, child_score, child_id));
+                }
+
+                max_width = max(max_width, width);
+            }
+
+            node_count += next.len();
+
+            // An unfinished first layer is still better than no move at all.
+            if out_of_budget(deadline, config, node_count) {
+                if depth == 0 {
+                    best = layer_best;
+                }
+
+                if config.verbose {
+                    eprintln!("TIMEOUT. Depth: {}, Width: {}, Nodes: {}, Saved: {}", depth, max_width, node_count, expander.saved());
+                }
+
+                break;
+            }
+
+            best = layer_best;
+            layer = next;
+            depth += 1;
+        }
+
+        if config.verbose {
+            eprintln!("Search Complete. Depth: {}, Width: {}, Nodes: {}, Saved: {}. Best: {:?}", depth, max_width, node_count, expander.saved(), (best.0.root_action, best.1));
+        }
+
+        stats.nodes = node_count;
+        stats.depth = depth;
+        stats.max_width = max_width;
+        tree.set_principal(best.2);
+
+        decide(&best.0, best.1, game)
+    }
+}
+
+/// Expands only the `beam_width` best states of each depth, which reaches far deeper than the
+/// breadth-first search in the same time.
+pub struct BeamSolutionFinder {
+    state_evaluator: Box<dyn StateEvaluator>,
+}
+
+impl BeamSolutionFinder {
+    pub fn new(state_evaluator: Box<dyn StateEvaluator>) -> BeamSolutionFinder {
+        BeamSolutionFinder { state_evaluator }
+    }
+}
+
+impl SolutionFinder for BeamSolutionFinder {
+    fn search(&self, game: &GameState, config: &BotConfig, deadline: &Instant, stats: &mut SearchStats, tree: &mut SearchTree) -> Decision {
+        let mut expander = Expander::new(game, config, &*self.state_evaluator, deadline, stats, tree);
+        let (root, root_score, root_id) = expander.root();
+        let mut beam = vec![(root, root_score, root_id)];
+
+        let mut node_count = 1;
+        let mut max_depth = 0;
+        let mut max_width = 0;
+        let mut best = (root, f32::MIN, root_id);
+
+        while max_depth < config.beam_depth && !out_of_budget(deadline, config, node_count) {
+            let mut next = Vec::new();
+            let mut layer_best = best;
+
+            for (state, parent_score, id) in &beam {
+                if is_game_over(state, game) || expander.is_superseded(state) {
+                    continue;
+                }
+
+                let mut width = 0;
+                for (child, score, child_id) in expander.expand(state, *parent_score, *id) {
+                    if layer_best.1 < score {
+                        layer_best = (child, score, child_id);
+                    }
+
+                    width += 1;
+                    next.push((child, score, child_id));
+                }
+
+                max_width = max(max_width, width);
+
+                if out_of_budget(deadline, config, node_count + next.len()) {
+                    break;
+                }
+            }
+
+            // Like the breadth-first search, only a complete layer may change the best move.
+            if out_of_budget(deadline, config, node_count + next.len()) && max_depth > 0 {
+                break;
+            }
+
+            best = layer_best;
+
+            if next.is_empty() {
+                break;
+            }
+
+            node_count += next.len();
+            max_depth += 1;
+
+            // Superseded states would only take up room in the beam.
+            next.retain(|(state, _, _)| !expander.is_superseded(state));
+            next.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            next.truncate(config.beam_width);
+            beam = next;
+        }
+
+        if config.verbose {
+            eprintln!("Beam Search Complete. Depth: {}, Nodes: {}, Saved: {}. Best: {:?}", max_depth, node_count, expander.saved(), (best.0.root_action, best.1));
+        }
+
+        stats.nodes = node_count;
+        stats.depth = max_depth;
+        stats.max_width = max_width;
+        tree.set_principal(best.2);
+
+        decide(&best.0, best.1, game)
+    }
+}
+
+/// Expands the best state found so far first, whatever its depth, so promising lines are followed
+/// down to `max_depth` before weaker ones are looked at.
+pub struct BestFirstSolutionFinder {
+    state_evaluator: Box<dyn StateEvaluator>,
+}
+
+impl BestFirstSolutionFinder {
+    pub fn new(state_evaluator: Box<dyn StateEvaluator>) -> BestFirstSolutionFinder {
+        BestFirstSolutionFinder { state_evaluator }
+    }
+}
+
+impl SolutionFinder for BestFirstSolutionFinder {
+    fn search(&self, game: &GameState, config: &BotConfig, deadline: &Instant, stats: &mut SearchStats, tree: &mut SearchTree) -> Decision {
+        let mut expander = Expander::new(game, config, &*self.state_evaluator, deadline, stats, tree);
+        let (root, root_score, root_id) = expander.root();
+        let mut queue = BinaryHeap::new();
+        queue.push(Frontier { state: root, score: root_score, id: root_id });
+
+        let mut node_count = 1;
+        let mut max_depth = 0;
+        let mut max_width = 0;
+        let mut best = (root, f32::MIN, root_id);
+
+        while let Some(Frontier { state, score, id }) = queue.pop() {
+            if out_of_budget(deadline, config, node_count) {
+                break;
+            }
+
+            max_depth = max(max_depth, state.depth);
+
+            if state.depth > config.max_depth || is_game_over(&state, game) || expander.is_superseded(&state) {
+                continue;
+            }
+
+            let mut width = 0;
+            for (child, child_score, child_id) in expander.expand(&state, score, id) {
+                if best.1 < child_score {
+                    best = (child, child_score, child_id);
+                }
+
+                width += 1;
+                queue.push(Frontier { state: child, score: child_score, id: child_id });
+            }
+
+            node_count += width;
+            max_width = max(max_width, width);
+        }
+
+        if config.verbose {
+            eprintln!("Best-First Search Complete. Depth: {}, Nodes: {}, Saved: {}. Best: {:?}", max_depth, node_count, expander.saved(), (best.0.root_action, best.1));
+        }
+
+        stats.nodes = node_count;
+        stats.depth = max_depth;
+        stats.max_width = max_width;
+        tree.set_principal(best.2);
+
+        decide(&best.0, best.1, game)
+    }
+}
+
+/// A state waiting in the best-first queue, ordered by its score.
+struct Frontier {
+    state: State,
+    score: f32,
+    id: usize,
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Frontier) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Frontier) -> Ordering {
+        self.score.partial_cmp(&other.score).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Frontier) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Adds `child`, reached from the node `parent_id` holding `parent`, to the tree, see
+/// `SearchTree::add`. `total` is the child's `score()` plus its `cumulative_score`, as the searches
+/// rank it.
+fn record(tree: &mut SearchTree, parent_id: usize, parent: &State, child: &State, total: f32, game: &GameState) -> usize {
+    if !tree.has_room() {
+        return parent_id;
+    }
+
+    let action = last_action(parent, child, game);
+    tree.add(parent_id, action, child.depth, child.cumulative_score, total - child.cumulative_score)
+}
+
+/// The action `get_children` took from `parent` to `child`. States only remember the root action,
+/// so it is told apart by what changed.
+fn last_action(parent: &State, child: &State, game: &GameState) -> Action {
+    let brewed = child.brewed_potions & !parent.brewed_potions;
+    let learned = child.new_spells & !parent.new_spells;
+    let cast = child.disabled_spells & !parent.disabled_spells;
+    let cast_new = child.disabled_new_spells & !parent.disabled_new_spells;
+
+    // The cast count is whichever one leads from the parent's ingredients to the child's.
+    let times_cast = |spell: &Spell| (1..=MAX_INGREDIENTS)
+        .find(|times| parent.ingredients.pay(Delta::pack(&spell.delta).times(*times)) == Some(child.ingredients))
+        .unwrap_or(1);
+
+    if brewed != 0 {
+        game.potions.iter().nth(brewed.trailing_zeros() as usize).map_or(Wait, |potion| Brew(potion.id))
+    } else if learned != 0 {
+        game.tome_spells.get(learned.trailing_zeros() as usize).map_or(Wait, |spell| Learn(spell.id))
+    } else if cast != 0 {
+        game.my_cast.get(cast.trailing_zeros() as usize).map_or(Wait, |spell| Cast(spell.id, times_cast(spell)))
+    } else if cast_new != 0 {
+        game.tome_spells.get(cast_new.trailing_zeros() as usize).map_or(Wait, |spell| Cast(spell.id, times_cast(spell)))
+    } else if child.disabled_spell_count() < parent.disabled_spell_count() {
+        Rest
+    } else {
+        Wait
+    }
+}
+
+/// The root action of the best line found, explained by the potions the line brews, or the one it
+/// heads for, and its score.
+fn decide(best: &State, score: f32, game: &GameState) -> Decision {
+    if best.depth == 0 {
+        return Decision::from(best.root_action);
+    }
+
+    let brewed: Vec<String> = game.potions.iter().enumerate()
+        .filter(|(p, _)| best.brewed_potions & (1 << p) != 0)
+        .map(|(_, potion)| potion.id.to_string())
+        .collect();
+
+    let plan = match brewed.is_empty() {
+        false => format!("brew {}", brewed.join("+")),
+        true => match target_potion(best, game) {
+            Some((potion, turns)) => format!("{} in {}", potion.id, turns),
+            None => "no potion".to_string(),
+        },
+    };
+
+    Decision {
+        action: best.root_action,
+        message: Some(format!("{} d{} {:.1}", plan, best.depth, score)),
+    }
+}
+
+/// The unbrewed potion earning the most per turn spent getting it, like in `score`, and how many
+/// turns that takes.
+fn target_potion<'a>(state: &State, game: &'a GameState) -> Option<(&'a Potion, u8)> {
+    let turns_to_brew = game.turns_to_brew(state.ingredients, state.disabled_spells);
+    let rate = |(potion, turns): &(&Potion, u8)| potion.base_price() as f32 / *turns as f32;
+
+    game.potions.iter().enumerate()
+        .filter(|(p, _)| state.brewed_potions & (1 << p) == 0)
+        .filter_map(|(p, potion)| turns_to_brew.get(p).copied().flatten().map(|turns| (potion, turns)))
+        .max_by(|a, b| rate(a).partial_cmp(&rate(b)).unwrap_or(Ordering::Equal))
+}
+
+/// True when either player can reach the sixth potion or the last turn within the search horizon.
+/// Back-to-back brews are legal, so we may brew on every turn of the horizon.
+fn is_endgame(game: &GameState, horizon: i32) -> bool {
+    game.my_brew_count + horizon >= MAX_BREWS
+        || game.opp_brew_count + 1 >= MAX_BREWS
+        || game.turn_count + horizon >= MAX_TURNS
+}
+
+/// Whether the opponent brews their last potion within `depth` turns from now.
+fn opp_finishes_by(game: &GameState, depth: i32) -> bool {
+    game.opp_brew_count + 1 >= MAX_BREWS && game.opp_brew_turns.values().any(|turns| *turns <= depth)
+}
+
+fn is_game_over(state: &State, game: &GameState) -> bool {
+    if !game.endgame || state.depth == 0 {
+        return false;
+    }
+
+    game.my_brew_count + state.brewed_potions.count_ones() as i32 >= MAX_BREWS
+        || game.turn_count + state.depth > MAX_TURNS
+        || opp_finishes_by(game, state.depth)
+}
+
+/// Scores a finished game by the final rupee margin, leftover tier-1+ ingredients included.
+fn endgame_score(state: &State, game: &GameState, params: &EvalParams) -> f32 {
+    let my_final = state.my_rupees + state.ingredients.tier(1) + state.ingredients.tier(2) + state.ingredients.tier(3);
+    let mut opp_final = game.opp_rupees + game.opp_ingredients[1] + game.opp_ingredients[2] + game.opp_ingredients[3];
+
+    // If the opponent is the one ending the game, they do it with the best potion they can reach in time.
+    if opp_finishes_by(game, state.depth) {
+        opp_final += game.potions.iter()
+            .filter(|potion| matches!(game.opp_brew_turns.get(&potion.id), Some(turns) if *turns <= state.depth))
+            .map(|potion| potion.price)
+            .max()
+            .unwrap_or(0);
+    }
+
+    let margin = my_final - opp_final;
+    margin.signum() as f32 * ENDGAME_WIN + margin as f32 * params.endgame_margin_weight
+}
+
+fn score(state: &State, game: &GameState, params: &EvalParams) -> f32 {
+    if is_game_over(state, game) {
+        return endgame_score(state, game, params);
+    }
+
+    let mut score = 0.0;
+
+    let new_spell_count = state.new_spells.count_ones() as f32;
+    let disabled_spell_count = game.my_disabled_spells.len() as f32 + state.disabled_spell_count() as f32;
+    let disabled_spell_ratio = disabled_spell_count / (game.my_cast.len() as f32 + new_spell_count);
+
+    //We want to have castable spells so punish more when spells to cast are less
+    //score -= disabled_spell_count;// / * 50.0 * (game.turn_count as f32 * 0.001);
+    score -= disabled_spell_ratio * (game.turn_count as f32 * params.disabled_spell_turn_rate) * params.disabled_spell_weight;
+
+    score += new_spell_count * (1.0 / (game.turn_count as f32 * params.learn_turn_rate));
+    score -= new_spell_count * (game.turn_count as f32 * params.learn_decay_rate);
+
+    //TODO: Testing
+    //score += (state.my_rupees - game.opp_rupees) as f32 * max(game.opp_brew_count, game.my_brew_count + state.brewed_potions.len() as i32) as f32;
+
+
+    let tier_weights = params.tier_weights();
+    let turns_to_brew = game.turns_to_brew(state.ingredients, state.disabled_spells);
+    let mut best_brew_rate: f32 = 0.0;
+    let mut brewed_base_price = 0;
+
+    for (p, potion) in game.potions.iter().enumerate() {
+        if state.brewed_potions & (1 << p) == 0 {
+            // The next brew is a turn away at best, the opponent may well have taken it by then.
+            if potion.is_taken_before(state.depth + 1, game) {
+                continue;
+            }
+
+            // What the potion earns per turn spent getting it
+            if let Some(turns) = turns_to_brew.get(p).copied().flatten() {
+                best_brew_rate = best_brew_rate.max(potion.base_price() as f32 / turns as f32);
+            }
+
+            // The price per turn is blind past `BREW_HORIZON` and can't tell apart inventories the
+            // same number of turns away, so this still steers the search in between. Scaling it
+            // down by 2-4x against `brew_rate_weight` lost self-play series, so both stay.
+            for (i, tier_weight) in tier_weights.iter().enumerate() {
+                // We want to be able to brew potions. The higher the ingredient tier, the better
+                score += (state.ingredients.tier(i) + potion.delta[i]) as f32 * potion.base_price() as f32 * tier_weight;
+            }
+
+            continue;
+        }
+
+        //score += 2000.0;// * game.turn_count as f32 * 0.3;
+        score += potion.base_price() as f32 * params.brew_weight; //* (game.turn_count as f32 * 0.067);
+        brewed_base_price += potion.base_price();
+//        let max_brewed = max(game.opp_brew_count, game.my_brew_count + state.brewed_potions.len() as i32) as f32;
+//
+//        if max_brewed >= 4.0 {
+//            score += max_brewed - 4.0 * 10.0;
+//        }
+    }
+
+    // Potions are brewed one at a time, so only the best one to go for next counts
+    score += best_brew_rate * params.brew_rate_weight;
+
+    // Whatever we earned above the base prices is urgency bonus we got to before the opponent did.
+    let urgency_bonus = state.my_rupees - game.my_rupees - brewed_base_price;
+    score += urgency_bonus as f32 * params.brew_weight;
+
+    score
+}
+
+fn get_children(state: &State, game: &GameState, parent_score: f32, deadline: &Instant) -> Vec<State> {
+    let mut new_states = Vec::new();
+    let child = State {
+        depth: state.depth + 1,
+        cumulative_score: parent_score,
+        ..*state
+    };
+
+    //TODO: Testing
+//    if state.brewed_potions.count_ones() as i32 + game.my_brew_count >= 6 {
+//        //eprintln!("End of game.");
+//        return new_states;
+//    }
+
+    if state.disabled_spell_count() > 0 {
+        new_states.push(State {
+            disabled_spells: 0,
+            disabled_new_spells: 0,
+            root_action: match state.root_action {
+                Wait => Rest,
+                _ => state.root_action,
+            },
+            ..child
+        });
+    }
+
+    for (p, potion) in game.potions.iter().enumerate() {
+        if is_timeout(deadline) {
+            break;
+        }
+
+        if state.brewed_potions & (1 << p) != 0 {// || game.turn_count < 15
+            continue;
+        }
+
+        if potion.is_taken_before(child.depth, game) {
+            continue;
+        }
+
+        if let Some(ingredients) = state.ingredients.pay(Delta::pack(&potion.delta)) {
+            new_states.push(State {
+                ingredients,
+                brewed_potions: state.brewed_potions | 1 << p,
+                my_rupees: state.my_rupees + potion.price,
+                root_action: match state.root_action {
+                    Wait => Brew(potion.id),
+                    _ => state.root_action,
+                },
+                ..child
+            });
+        }
+    }
+
+    for (s, spell) in game.my_cast.iter().enumerate().take(64) {
+        if is_timeout(deadline) {
+            break;
+        }
+
+        if !spell.castable || state.disabled_spells & (1 << s) != 0 {
+            continue;
+        }
+
+        let delta = Delta::pack(&spell.delta);
+
+        for times in 1..4 {
+            let paid = match &game.transitions {
+                Some(transitions) => transitions.cast(s, times, state.ingredients),
+                None => state.ingredients.pay(delta.times(times)),
+            };
+
+            if let Some(ingredients) = paid {
+                new_states.push(State {
+                    ingredients,
+                    disabled_spells: state.disabled_spells | 1 << s,
+                    root_action: match state.root_action {
+                        Wait => Cast(spell.id, times),
+                        _ => state.root_action,
+                    },
+                    ..child
+                });
+            }
+
+            if !spell.repeatable {
+                break;
+            }
+        }
+    }
+
+    for (s, spell) in game.tome_spells.iter().enumerate().take(8) {
+        if is_timeout(deadline) {
+            break;
+        }
+
+        if state.disabled_new_spells & (1 << s) != 0 {
+            continue;
+        }
+
+        if state.new_spells & (1 << s) != 0 {
+            //Castable
+            let delta = Delta::pack(&spell.delta);
+
+            for times in 1..5 {
+                if let Some(ingredients) = state.ingredients.pay(delta.times(times)) {
+                    new_states.push(State {
+                        ingredients,
+                        disabled_new_spells: state.disabled_new_spells | 1 << s,
+                        root_action: state.root_action, //Special case since it was already not castable. i.e not original
+                        ..child
+                    });
+                }
+
+                if !spell.repeatable {
+                    break;
+                }
+            }
+
+            continue;
+        }
+//
+//        if game.turn_count >= 15 {
+//            continue;
+//        }
+
+        //Learn
+        // Spells learned earlier in the line no longer sit in front of this one, and the tax is one
+        // tier-0 ingredient deposited on each spell that still does.
+        let mut in_front = 0u8;
+        for (t, other) in game.tome_spells.iter().enumerate().take(8) {
+            if state.new_spells & (1 << t) == 0 && other.read_ahead_tax < spell.read_ahead_tax {
+                in_front |= 1 << t;
+            }
+        }
+
+        if let Some(ingredients) = state.ingredients.pay(Delta::pack(&[-(in_front.count_ones() as i32), 0, 0, 0])) {
+            let mut tome_tax = state.tome_tax;
+            for (t, tax) in tome_tax.iter_mut().enumerate() {
+                if in_front & (1 << t) != 0 {
+                    *tax = tax.saturating_add(1);
+                }
+            }
+
+            let collected = tome_tax[s] as i32;
+            tome_tax[s] = 0;
+
+            new_states.push(State {
+                ingredients: ingredients.add_tier_0(collected),
+                new_spells: state.new_spells | 1 << s,
+                tome_tax,
+                root_action: match state.root_action {
+                    Wait => Learn(spell.id),
+                    _ => state.root_action,
+                },
+                ..child
+            });
+        }
+    }
+
+    new_states
+}
+
+/// Array form of `Ingredients::pay`, for code outside the search.
+pub fn pay(cost: &[i32; 4], money: &[i32; 4]) -> Option<[i32; 4]> {
+    Ingredients::pack(money).pay(Delta::pack(cost)).map(Ingredients::unpack)
+}
+
+fn is_timeout(deadline: &Instant) -> bool {
+    Instant::now() >= *deadline
+}
+
+/// Whether a search that has generated `nodes` nodes has to stop, see `BotConfig::node_limit`.
+fn out_of_budget(deadline: &Instant, config: &BotConfig, nodes: usize) -> bool {
+    (config.node_limit > 0 && nodes >= config.node_limit) || is_timeout(deadline)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::parse_turn;
+
+    const STARTING_SPELLS: [&str; 4] = ["2 0 0 0", "-1 1 0 0", "0 -1 1 0", "0 0 -1 1"];
+
+    /// A turn with `lines` on the board and both players' starting spells, as the bot sees it on
+    /// turn `turn`. Inventories are `inv0 inv1 inv2 inv3 score`.
+    fn position(turn: i32, lines: &[&str], mine: &str, theirs: &str) -> GameState {
+        let mut lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+        for (i, delta) in STARTING_SPELLS.iter().enumerate() {
+            lines.push(format!("{} CAST {} 0 -1 -1 1 0", 78 + i, delta));
+            lines.push(format!("{} OPPONENT_CAST {} 0 -1 -1 1 0", 82 + i, delta));
+        }
+
+        let mut game = parse_turn(&format!("{}\n{}\n{}\n{}\n", lines.len(), lines.join("\n"), mine, theirs)).unwrap();
+        let mut bot = Bot::with_config(BotConfig { verbose: false, ..BotConfig::default() });
+
+        bot.set_turn(turn);
+        bot.observe(&mut game);
+        game
+    }
+
+    fn children(state: &State, game: &GameState) -> Vec<State> {
+        get_children(state, game, 0.0, &(Instant::now() + Duration::from_secs(60)))
+    }
+
+    fn child(state: &State, game: &GameState, action: Action) -> State {
+        children(state, game).into_iter()
+            .find(|child| last_action(state, child, game) == action)
+            .unwrap_or_else(|| panic!("{:?} isn't a child", action))
+    }
+
+    /// The ids of the potions brewed by the children of `state`.
+    fn brews(state: &State, game: &GameState) -> Vec<i32> {
+        let mut brews: Vec<i32> = children(state, game).iter()
+            .filter_map(|child| match last_action(state, child, game) {
+                Brew(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+
+        brews.sort_unstable();
+        brews
+    }
+
+    const CONTESTED: [&str; 2] = ["42 BREW -2 0 0 0 6 0 0 0 0", "43 BREW 0 0 0 -2 16 0 0 0 0"];
+
+    #[test]
+    fn brews_a_contested_potion_only_on_the_first_turn() {
+        // The opponent can brew 42 right away but can't get to 43.
+        let game = position(10, &CONTESTED, "2 0 0 2 0", "2 0 0 0 0");
+        let root = root_state(&game);
+
+        assert_eq!(game.opp_brew_turns.get(&42), Some(&1));
+        assert_eq!(brews(&root, &game), vec![42, 43]);
+        assert_eq!(brews(&child(&root, &game, Cast(78, 1)), &game), vec![43]);
+    }
+
+    #[test]
+    fn endgame_starts_when_brewing_every_turn_reaches_the_sixth_potion() {
+        let mut game = position(10, &CONTESTED, "2 0 0 2 0", "0 0 0 0 0");
+
+        game.my_brew_count = 1;
+        assert!(!is_endgame(&game, 4));
+
+        game.my_brew_count = 2;
+        assert!(is_endgame(&game, 4));
+    }
+
+    #[test]
+    fn game_is_over_at_our_sixth_brew() {
+        let mut game = position(10, &CONTESTED, "2 0 0 2 0", "0 0 0 0 0");
+        game.my_brew_count = 5;
+        game.endgame = true;
+        let root = root_state(&game);
+
+        assert!(!is_game_over(&root, &game));
+        assert!(is_game_over(&child(&root, &game, Brew(43)), &game));
+        assert!(!is_game_over(&child(&root, &game, Cast(78, 1)), &game));
+    }
+
+    #[test]
+    fn game_is_over_after_the_last_turn() {
+        let mut game = position(99, &CONTESTED, "2 0 0 2 0", "0 0 0 0 0");
+        game.endgame = true;
+        let state = child(&root_state(&game), &game, Cast(78, 1));
+
+        assert!(!is_game_over(&state, &game));
+        assert!(is_game_over(&child(&state, &game, Cast(79, 1)), &game));
+    }
+
+    #[test]
+    fn game_is_over_when_the_opponent_can_brew_their_sixth_potion() {
+        let mut game = position(10, &CONTESTED, "2 0 0 2 0", "2 0 0 0 0");
+        game.opp_brew_count = 5;
+        game.endgame = true;
+
+        assert!(is_game_over(&child(&root_state(&game), &game, Cast(78, 1)), &game));
+    }
+
+    #[test]
+    fn endgame_score_goes_by_the_final_margin() {
+        let params = EvalParams::default();
+        // Tier-1+ ingredients count a rupee each: 10 + 2 against 9 + 3.
+        let tie = position(10, &CONTESTED, "2 0 1 1 10", "0 1 1 1 9");
+        let win = position(10, &CONTESTED, "2 0 1 1 11", "0 1 1 1 9");
+        let loss = position(10, &CONTESTED, "2 0 1 1 9", "0 1 1 1 9");
+
+        assert_eq!(endgame_score(&root_state(&tie), &tie, &params), 0.0);
+        assert_eq!(endgame_score(&root_state(&win), &win, &params), ENDGAME_WIN + params.endgame_margin_weight);
+        assert_eq!(endgame_score(&root_state(&loss), &loss, &params), -ENDGAME_WIN - params.endgame_margin_weight);
+    }
+
+    #[test]
+    fn endgame_score_counts_the_potion_the_opponent_finishes_with() {
+        let params = EvalParams::default();
+        let mut game = position(10, &CONTESTED, "2 0 0 2 10", "2 0 0 0 10");
+        game.opp_brew_count = 5;
+        let state = child(&root_state(&game), &game, Cast(78, 1));
+
+        // They brew 42 and finish on 16, against our 10 rupees and 2 tier-3 ingredients.
+        assert_eq!(endgame_score(&state, &game, &params), -ENDGAME_WIN - 4.0 * params.endgame_margin_weight);
+    }
+
+    #[test]
+    fn a_better_duplicate_supersedes_the_queued_copy() {
+        let game = position(10, &CONTESTED, "2 0 0 2 0", "0 0 0 0 0");
+        let cast = child(&root_state(&game), &game, Cast(78, 1));
+        let better = State { cumulative_score: cast.cumulative_score + 100.0, ..cast };
+        let mut table = TranspositionTable::new(true);
+
+        assert!(table.insert(&cast));
+        assert!(!table.is_superseded(&cast));
+        assert!(table.insert(&better));
+        assert!(table.is_superseded(&cast));
+        assert!(!table.is_superseded(&better));
+        // Worse or deeper copies are dropped straight away.
+        assert!(!table.insert(&cast));
+        assert!(!table.insert(&State { depth: cast.depth + 1, ..better }));
+        assert_eq!(table.saved, 3);
+    }
+
+    const TOME: [&str; 4] = [
+        "15 LEARN 0 2 0 0 0 0 0 0 0",
+        "14 LEARN 0 0 0 1 0 1 0 0 0",
+        "4 LEARN 3 0 0 0 0 2 0 0 0",
+        "12 LEARN 2 1 0 0 0 3 0 0 0",
+    ];
+
+    #[test]
+    fn collects_the_tax_deposited_earlier_in_the_line() {
+        let game = position(5, &TOME, "3 0 0 0 0", "3 0 0 0 0");
+        let root = root_state(&game);
+
+        // Two spells in front, one ingredient deposited on each.
+        let learned = child(&root, &game, Learn(4));
+        assert_eq!(learned.ingredients.unpack(), [1, 0, 0, 0]);
+        assert_eq!(learned.tome_tax[..4], [1, 1, 0, 0]);
+
+        // Nothing in front, and our own deposit comes back.
+        let learned = child(&learned, &game, Learn(15));
+        assert_eq!(learned.ingredients.unpack(), [2, 0, 0, 0]);
+        assert_eq!(learned.tome_tax[..4], [0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn learning_a_spell_in_front_makes_the_others_cheaper() {
+        let game = position(5, &TOME, "3 0 0 0 0", "3 0 0 0 0");
+        let root = root_state(&game);
+
+        assert_eq!(child(&root, &game, Learn(12)).ingredients.unpack(), [0, 0, 0, 0]);
+
+        let learned = child(&root, &game, Learn(14));
+        assert_eq!(learned.ingredients.unpack(), [2, 0, 0, 0]);
+        assert_eq!(child(&learned, &game, Learn(12)).ingredients.unpack(), [0, 0, 0, 0]);
+        assert_eq!(child(&learned, &game, Learn(4)).ingredients.unpack(), [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn contested_potions_add_nothing_to_the_score() {
+        let contested = position(10, &CONTESTED, "2 0 0 2 0", "2 0 0 0 0");
+        // Here the opponent needs a cast first, so 42 is still there when we brew it next turn.
+        let uncontested = position(10, &CONTESTED, "2 0 0 2 0", "0 0 0 0 0");
+        let state = child(&root_state(&contested), &contested, Cast(78, 1));
+        let params = EvalParams::default();
+
+        assert_eq!(uncontested.opp_brew_turns.get(&42), Some(&2));
+        assert!(score(&state, &contested, &params) < score(&state, &uncontested, &params));
+    }
+}
@@ -0,0 +1,234 @@
+//! Monte Carlo tree search.
+//!
+//! Nodes are picked with UCT, expanded through `get_children` and valued by a rollout to a fixed
+//! horizon that brews whenever it can and otherwise plays a random move. The reward is the rupees
+//! gained on the way, scaled by the range of rewards seen so far before UCT compares it with the
+//! exploration term.
+//!
+//! A subtree whose every line has been played out to the horizon or the end of the game is solved:
+//! visiting it again would only replay the same rewards, so selection skips it and the search stops
+//! early once the root is solved.
+
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::Action::Wait;
+use crate::search_tree::{SearchTree, UNRECORDED};
+use crate::telemetry::SearchStats;
+use crate::{get_children, is_game_over, out_of_budget, record, root_state, BotConfig, Decision, GameState, SolutionFinder, State, StateEvaluator};
+
+struct Node {
+    state: State,
+    children: Vec<usize>,
+    /// Children generated but not added to the tree yet.
+    untried: Vec<State>,
+    expanded: bool,
+    /// Expanded with nothing untried and every child solved, so there is nothing left to learn below.
+    solved: bool,
+    visits: u32,
+    total_reward: f32,
+}
+
+impl Node {
+    fn new(state: State) -> Node {
+        Node {
+            state,
+            children: Vec::new(),
+            untried: Vec::new(),
+            expanded: false,
+            solved: false,
+            visits: 0,
+            total_reward: 0.0,
+        }
+    }
+
+    fn mean_reward(&self) -> f32 {
+        self.total_reward / self.visits as f32
+    }
+}
+
+/// The state evaluator only labels the nodes of an exported tree, the search itself goes by
+/// rollout rewards.
+pub struct MonteCarloSolutionFinder {
+    state_evaluator: Box<dyn StateEvaluator>,
+}
+
+impl MonteCarloSolutionFinder {
+    pub fn new(state_evaluator: Box<dyn StateEvaluator>) -> MonteCarloSolutionFinder {
+        MonteCarloSolutionFinder { state_evaluator }
+    }
+}
+
+impl SolutionFinder for MonteCarloSolutionFinder {
+    /// Returns the most visited root move once the deadline passes, with its visits and mean reward.
+    fn search(&self, game: &GameState, config: &BotConfig, deadline: &Instant, stats: &mut SearchStats, tree: &mut SearchTree) -> Decision {
+        // Seeded by the turn so replays of a recorded match pick the same moves.
+        let mut rng = StdRng::seed_from_u64(game.turn_count as u64);
+        let mut nodes = vec![Node::new(root_state(game))];
+        let mut iterations = 0;
+        let mut rewards = (f32::INFINITY, f32::NEG_INFINITY);
+
+        while !nodes[0].solved && !out_of_budget(deadline, config, nodes.len()) {
+            let mut path = vec![0];
+            let mut current = 0;
+
+            // Selection
+            loop {
+                if !nodes[current].expanded {
+                    let state = &nodes[current].state;
+                    let untried = match state.depth < config.mcts_horizon && !is_game_over(state, game) {
+                        true => get_children(state, game, 0.0, deadline),
+                        false => Vec::new(),
+                    };
+
+                    nodes[current].untried = untried;
+                    nodes[current].expanded = true;
+                }
+
+                if !nodes[current].untried.is_empty() || nodes[current].children.is_empty() {
+                    break;
+                }
+
+                current = select_child(&nodes, current, config.mcts_exploration, rewards.1 - rewards.0);
+                path.push(current);
+            }
+
+            // Expansion
+            if !nodes[current].untried.is_empty() {
+                let index = rng.gen_range(0, nodes[current].untried.len());
+                let state = nodes[current].untried.swap_remove(index);
+
+                nodes.push(Node::new(state));
+                let child = nodes.len() - 1;
+                nodes[current].children.push(child);
+                path.push(child);
+                current = child;
+            }
+
+            let reward = rollout(&nodes[current].state, game, config, deadline, &mut rng);
+            rewards = (rewards.0.min(reward), rewards.1.max(reward));
+
+            for &index in path.iter().rev() {
+                let solved = nodes[index].expanded
+                    && nodes[index].untried.is_empty()
+                    && nodes[index].children.iter().all(|&child| nodes[child].solved);
+
+                nodes[index].visits += 1;
+                nodes[index].total_reward += reward;
+                nodes[index].solved = solved;
+            }
+
+            iterations += 1;
+        }
+
+        let best = nodes[0].children.iter().max_by_key(|&&child| nodes[child].visits);
+
+        stats.nodes = nodes.len();
+        stats.depth = nodes.iter().map(|node| node.state.depth).max().unwrap_or(0);
+        stats.max_width = nodes.iter().map(|node| node.children.len()).max().unwrap_or(0);
+
+        for &child in &nodes[0].children {
+            stats.candidate(nodes[child].state.root_action, nodes[child].mean_reward());
+        }
+
+        if tree.has_room() {
+            export(&nodes, game, self.state_evaluator.as_ref(), tree);
+        }
+
+        if config.verbose {
+            eprintln!("MCTS Complete. Iterations: {}, Nodes: {}", iterations, nodes.len());
+
+            for &child in &nodes[0].children {
+                let node = &nodes[child];
+                eprintln!("{:?}: {} visits, {:.2} mean reward", node.state.root_action, node.visits, node.mean_reward());
+            }
+        }
+
+        match best {
+            Some(&child) => {
+                let node = &nodes[child];
+
+                Decision {
+                    action: node.state.root_action,
+                    message: Some(format!("{} visits {:.1}", node.visits, node.mean_reward())),
+                }
+            }
+            None => Decision::from(Wait),
+        }
+    }
+}
+
+/// Adds the nodes to `tree` breadth first, with the most visited line as the principal one.
+fn export(nodes: &[Node], game: &GameState, state_evaluator: &dyn StateEvaluator, tree: &mut SearchTree) {
+    let mut ids = vec![UNRECORDED; nodes.len()];
+    ids[0] = tree.add(UNRECORDED, Wait, 0, 0.0, state_evaluator.evaluate(&nodes[0].state, game));
+
+    let mut queue = vec![0];
+    let mut next = 0;
+
+    while next < queue.len() {
+        let parent = queue[next];
+        next += 1;
+
+        for &child in &nodes[parent].children {
+            let state = &nodes[child].state;
+            ids[child] = record(tree, ids[parent], &nodes[parent].state, state, state_evaluator.evaluate(state, game), game);
+            queue.push(child);
+        }
+    }
+
+    let mut principal = 0;
+    while let Some(&child) = nodes[principal].children.iter().max_by_key(|&&child| nodes[child].visits) {
+        principal = child;
+    }
+
+    tree.set_principal(ids[principal]);
+}
+
+/// UCT over the unsolved children: the mean reward, divided by `reward_range` so `exploration`
+/// means the same whatever the rupees at stake, plus an exploration term that favours children
+/// visited less often.
+fn select_child(nodes: &[Node], parent: usize, exploration: f32, reward_range: f32) -> usize {
+    let log_visits = (nodes[parent].visits as f32).ln();
+    let scale = reward_range.max(1.0);
+    let uct = |child: usize| {
+        let node = &nodes[child];
+        node.mean_reward() / scale + exploration * (log_visits / node.visits as f32).sqrt()
+    };
+
+    // The parent isn't solved, so some child isn't either.
+    *nodes[parent].children.iter()
+        .filter(|&&child| !nodes[child].solved)
+        .max_by(|&&a, &&b| uct(a).partial_cmp(&uct(b)).unwrap())
+        .unwrap()
+}
+
+/// Plays on from `state` until the horizon and returns the rupees gained since the root.
+fn rollout(state: &State, game: &GameState, config: &BotConfig, deadline: &Instant, rng: &mut StdRng) -> f32 {
+    let mut current = None;
+
+    loop {
+        let state = current.as_ref().unwrap_or(state);
+
+        if state.depth >= config.mcts_horizon || is_game_over(state, game) {
+            return (state.my_rupees - game.my_rupees) as f32;
+        }
+
+        let mut children = get_children(state, game, 0.0, deadline);
+
+        if children.is_empty() {
+            return (state.my_rupees - game.my_rupees) as f32;
+        }
+
+        // Brewing is always worth it within a rollout, otherwise any move will do.
+        let brew = children.iter().enumerate()
+            .filter(|(_, child)| child.my_rupees > state.my_rupees)
+            .max_by_key(|(_, child)| child.my_rupees)
+            .map(|(i, _)| i);
+        let index = brew.unwrap_or_else(|| rng.gen_range(0, children.len()));
+
+        current = Some(children.swap_remove(index));
+    }
+}
@@ -0,0 +1,135 @@
+//! Opponent reachability analysis.
+//!
+//! Searches the opponent's spellbook from their current inventory to estimate how soon they can
+//! brew each potion on the board.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{pay, GameState};
+
+/// How many turns ahead we look for the opponent.
+const HORIZON: i32 = 5;
+/// Caps the work done per turn. The opponent tree is only a guess so it doesn't need to be exhaustive.
+const MAX_NODES: usize = 3000;
+
+struct OppState {
+    ingredients: [i32; 4],
+    /// Bit `i` is set when `opp_cast[i]` is exhausted.
+    exhausted: u64,
+    depth: i32,
+}
+
+/// For each potion the opponent can reach within the horizon, the number of turns until they can
+/// brew it, the brew itself included. A potion they can afford right now takes 1 turn.
+pub fn turns_to_brew(game: &GameState) -> HashMap<i32, i32> {
+    let mut turns = HashMap::new();
+
+    let mut exhausted = 0;
+    for (i, spell) in game.opp_cast.iter().enumerate().take(64) {
+        if !spell.castable {
+            exhausted |= 1 << i;
+        }
+    }
+
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    queue.push_back(OppState {
+        ingredients: game.opp_ingredients,
+        exhausted,
+        depth: 0,
+    });
+
+    while let Some(state) = queue.pop_front() {
+        for potion in &game.potions {
+            if !turns.contains_key(&potion.id) && pay(&potion.delta, &state.ingredients).is_some() {
+                turns.insert(potion.id, state.depth + 1);
+            }
+        }
+
+        if turns.len() == game.potions.len() || state.depth + 1 >= HORIZON || seen.len() >= MAX_NODES {
+            continue;
+        }
+
+        if state.exhausted != 0 && seen.insert((state.ingredients, 0)) {
+            queue.push_back(OppState {
+                ingredients: state.ingredients,
+                exhausted: 0,
+                depth: state.depth + 1,
+            });
+        }
+
+        for (i, spell) in game.opp_cast.iter().enumerate().take(64) {
+            if state.exhausted & (1 << i) != 0 {
+                continue;
+            }
+
+            for times in 1..4 {
+                let delta = [spell.delta[0] * times, spell.delta[1] * times, spell.delta[2] * times, spell.delta[3] * times];
+
+                match pay(&delta, &state.ingredients) {
+                    Some(ingredients) => {
+                        let exhausted = state.exhausted | (1 << i);
+
+                        if seen.insert((ingredients, exhausted)) {
+                            queue.push_back(OppState {
+                                ingredients,
+                                exhausted,
+                                depth: state.depth + 1,
+                            });
+                        }
+                    }
+                    None => break,
+                }
+
+                if !spell.repeatable {
+                    break;
+                }
+            }
+        }
+    }
+
+    turns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::parse_turn;
+
+    /// A turn where the opponent holds `inventory` and has the starting spells, castable or not.
+    fn game(potions: &[&str], inventory: &str, castable: i32) -> GameState {
+        let mut lines: Vec<String> = potions.iter().map(|potion| potion.to_string()).collect();
+
+        for (id, delta) in [(82, "2 0 0 0"), (83, "-1 1 0 0"), (84, "0 -1 1 0"), (85, "0 0 -1 1")].iter() {
+            lines.push(format!("{} OPPONENT_CAST {} 0 -1 -1 {} 0", id, delta, castable));
+        }
+
+        parse_turn(&format!("{}\n{}\n0 0 0 0 0\n{} 0\n", lines.len(), lines.join("\n"), inventory)).unwrap()
+    }
+
+    #[test]
+    fn counts_the_turns_including_the_brew() {
+        let game = game(&[
+            "42 BREW -3 0 0 0 6 0 0 0 0",
+            "43 BREW -2 -1 0 0 7 0 0 0 0",
+            "44 BREW -2 0 0 -1 9 0 0 0 0",
+            "45 BREW 0 0 0 -5 20 0 0 0 0",
+        ], "3 0 0 0", 1);
+
+        let turns = turns_to_brew(&game);
+
+        assert_eq!(turns.get(&42), Some(&1));
+        assert_eq!(turns.get(&43), Some(&2));
+        // Tier 0 up to tier 3 takes a cast per tier.
+        assert_eq!(turns.get(&44), Some(&4));
+        // Past the horizon.
+        assert_eq!(turns.get(&45), None);
+    }
+
+    #[test]
+    fn rests_before_casting_exhausted_spells() {
+        let game = game(&["43 BREW -2 -1 0 0 7 0 0 0 0"], "3 0 0 0", 0);
+
+        assert_eq!(turns_to_brew(&game).get(&43), Some(&3));
+    }
+}
@@ -0,0 +1,129 @@
+//! Evaluation weights used by `score()`.
+//!
+//! The defaults are the hand-tuned values the bot ships with. Any weight can be overridden at
+//! startup from a `key=value` file or from `EVAL_<KEY>` environment variables, e.g.
+//! `EVAL_BREW_WEIGHT=60`.
+
+use std::env;
+use std::fmt;
+use std::fs;
+
+const ENV_PREFIX: &str = "EVAL_";
+
+macro_rules! eval_params {
+    ($($(#[$doc:meta])* $field:ident: $default:expr,)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct EvalParams {
+            $($(#[$doc])* pub $field: f32,)*
+        }
+
+        impl Default for EvalParams {
+            fn default() -> Self {
+                EvalParams {
+                    $($field: $default,)*
+                }
+            }
+        }
+
+        impl EvalParams {
+            /// Every weight's key, in the order used by `to_vec` and `from_vec`.
+            pub const KEYS: &'static [&'static str] = &[$(stringify!($field),)*];
+
+            pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+                let value = value.trim().parse().map_err(|_| format!("invalid value for {}: {}", key, value))?;
+
+                match key {
+                    $(stringify!($field) => self.$field = value,)*
+                    _ => return Err(format!("unknown setting: {}", key)),
+                }
+
+                Ok(())
+            }
+
+            pub fn to_vec(&self) -> Vec<f32> {
+                vec![$(self.$field,)*]
+            }
+
+            /// Missing trailing values keep their defaults.
+            pub fn from_vec(values: &[f32]) -> EvalParams {
+                let mut values = values.iter();
+
+                EvalParams {
+                    $($field: values.next().copied().unwrap_or($default),)*
+                }
+            }
+        }
+    };
+}
+
+eval_params! {
+    /// Penalty for having spells exhausted, scaled by the share of exhausted spells.
+    disabled_spell_weight: 35.0,
+    /// How fast the exhausted spell penalty grows with the turn number.
+    disabled_spell_turn_rate: 0.05,
+    /// Reward per learned spell is `1 / (turn * learn_turn_rate)`, so learning early pays most.
+    learn_turn_rate: 0.001,
+    /// Penalty per learned spell is `turn * learn_decay_rate`.
+    learn_decay_rate: 0.001,
+    /// Weight of each ingredient tier when valuing progress towards a potion. Kept next to
+    /// `brew_rate_weight` on purpose, see `score`.
+    tier_0_weight: 0.6,
+    tier_1_weight: 1.2,
+    tier_2_weight: 1.8,
+    tier_3_weight: 2.4,
+    /// Reward per rupee earned by brewing.
+    brew_weight: 80.0,
+    /// Reward per rupee per turn of the best potion to brew next, see `GameState::turns_to_brew`.
+    brew_rate_weight: 40.0,
+    /// Reward per rupee of final margin once the game is over.
+    endgame_margin_weight: 100.0,
+}
+
+impl EvalParams {
+    pub fn tier_weights(&self) -> [f32; 4] {
+        [self.tier_0_weight, self.tier_1_weight, self.tier_2_weight, self.tier_3_weight]
+    }
+
+    /// Applies `key=value` lines. Blank lines and lines starting with `#` are skipped.
+    pub fn load(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected key=value, got {:?}", i + 1, line))?;
+            self.set(key.trim(), value).map_err(|error| format!("line {}: {}", i + 1, error))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+        self.load(&text)
+    }
+
+    /// Applies every `EVAL_<KEY>` environment variable that is set.
+    pub fn load_env(&mut self) -> Result<(), String> {
+        for key in EvalParams::KEYS {
+            if let Ok(value) = env::var(format!("{}{}", ENV_PREFIX, key.to_uppercase())) {
+                self.set(key, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the weights in the same `key=value` format `load` reads.
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in EvalParams::KEYS.iter().zip(self.to_vec()) {
+            writeln!(f, "{}={}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+//! Turn input protocol.
+//!
+//! Parses the arena's per-turn input into a `GameState`. Malformed input is reported with the line
+//! number, the field being read and the raw text instead of panicking, and EOF between turns is
+//! reported as the end of the match.
+
+use std::cell::RefCell;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::{GameState, Potion, Spell};
+
+const ACTION_FIELDS: [&str; 11] = [
+    "actionId", "actionType", "delta0", "delta1", "delta2", "delta3", "price", "tomeIndex", "taxCount", "castable",
+    "repeatable",
+];
+const INVENTORY_FIELDS: [&str; 5] = ["inv0", "inv1", "inv2", "inv3", "score"];
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    /// Input ended in the middle of a turn.
+    UnexpectedEof { line: usize },
+    MissingField { line: usize, field: &'static str, text: String },
+    InvalidField { line: usize, field: &'static str, text: String },
+    TrailingInput { line: usize, text: String },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Io(error) => write!(f, "failed to read input: {}", error),
+            ProtocolError::UnexpectedEof { line } => write!(f, "line {}: input ended in the middle of a turn", line),
+            ProtocolError::MissingField { line, field, text } => write!(f, "line {}: missing {} in {:?}", line, field, text),
+            ProtocolError::InvalidField { line, field, text } => write!(f, "line {}: invalid {} in {:?}", line, field, text),
+            ProtocolError::TrailingInput { line, text } => write!(f, "line {}: unexpected trailing input in {:?}", line, text),
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(error: io::Error) -> Self {
+        ProtocolError::Io(error)
+    }
+}
+
+pub struct TurnReader<R> {
+    input: R,
+    line: usize,
+    /// The raw text of the turn read last.
+    raw: String,
+}
+
+impl<R: BufRead> TurnReader<R> {
+    pub fn new(input: R) -> TurnReader<R> {
+        TurnReader { input, line: 0, raw: String::new() }
+    }
+
+    /// Reads one turn. Returns `Ok(None)` when the input ends before a new turn starts.
+    ///
+    /// Only the fields carried by the input are filled in. Match history such as brew counts is
+    /// added by `Bot::observe`.
+    pub fn read_turn(&mut self) -> Result<Option<GameState>, ProtocolError> {
+        self.raw.clear();
+
+        let text = match self.read_line()? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let received_at = Instant::now();
+
+        let mut game = GameState {
+            my_rupees: 0,
+            opp_rupees: 0,
+            my_ingredients: [0; 4],
+            opp_ingredients: [0; 4],
+            potions: BinaryHeap::new(),
+            my_cast: Vec::new(),
+            opp_cast: Vec::new(),
+            tome_spells: Vec::new(),
+            my_disabled_spells: HashSet::new(),
+            my_brew_count: 0,
+            opp_brew_count: 0,
+            turn_count: 0,
+            opp_brew_turns: HashMap::new(),
+            endgame: false,
+            received_at,
+            transitions: None,
+            casts_to_brew: HashMap::new(),
+            brew_turns: RefCell::new(HashMap::new()),
+        };
+
+        let action_count: usize = Line::new(&text, self.line, &["actionCount"])?.field(0)?; // the number of spells and recipes in play
+
+        for _ in 0..action_count {
+            let text = self.expect_line()?;
+            let line = Line::new(&text, self.line, &ACTION_FIELDS)?;
+
+            let action_id: i32 = line.field(0)?; // the unique ID of this spell or recipe
+            let action_type: String = line.field(1)?; // CAST, OPPONENT_CAST, LEARN or BREW
+            let delta = [line.field(2)?, line.field(3)?, line.field(4)?, line.field(5)?]; // tier-0 to tier-3 ingredient change
+            let price: i32 = line.field(6)?; // the price in rupees if this is a potion
+            let tome_index: i32 = line.field(7)?; // the index in the tome if this is a tome spell, equal to the read-ahead tax; For brews, this is the value of the current urgency bonus
+            let tax_count: i32 = line.field(8)?; // the amount of taxed tier-0 ingredients you gain from learning this spell; For brews, this is how many times you can still gain an urgency bonus
+            let castable: i32 = line.field(9)?; // 1 if this is a castable player spell
+            let repeatable: i32 = line.field(10)?; // 1 if this is a repeatable player spell
+
+            let spell = Spell {
+                id: action_id,
+                delta,
+                read_ahead_tax: tome_index,
+                tax_count,
+                castable: castable == 1,
+                repeatable: repeatable == 1,
+            };
+
+            match &action_type[..] {
+                "BREW" => game.potions.push(Potion {
+                    id: action_id,
+                    delta,
+                    price,
+                    urgency_bonus: tome_index,
+                    urgency_bonus_count: tax_count,
+                }),
+                "CAST" => {
+                    if !spell.castable {
+                        game.my_disabled_spells.insert(action_id);
+                    }
+
+                    game.my_cast.push(spell);
+                }
+                "OPPONENT_CAST" => game.opp_cast.push(spell),
+                "LEARN" => game.tome_spells.push(spell),
+                _ => return Err(line.invalid(1)),
+            }
+        }
+
+        for i in 0..2 {
+            let text = self.expect_line()?;
+            let line = Line::new(&text, self.line, &INVENTORY_FIELDS)?;
+
+            let ingredients = [line.field(0)?, line.field(1)?, line.field(2)?, line.field(3)?]; // tier-0 to tier-3 ingredients in inventory
+            let score = line.field(4)?; // amount of rupees
+
+            if i == 0 {
+                game.my_ingredients = ingredients;
+                game.my_rupees = score;
+            } else {
+                game.opp_ingredients = ingredients;
+                game.opp_rupees = score;
+            }
+        }
+
+        Ok(Some(game))
+    }
+
+    /// The turn input exactly as it was read, e.g. for recording it.
+    pub fn last_input(&self) -> &str {
+        &self.raw
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, ProtocolError> {
+        let mut text = String::new();
+
+        if self.input.read_line(&mut text)? == 0 {
+            return Ok(None);
+        }
+
+        self.line += 1;
+        self.raw.push_str(&text);
+
+        if !text.ends_with('\n') {
+            self.raw.push('\n');
+        }
+        Ok(Some(text.trim_end().to_string()))
+    }
+
+    fn expect_line(&mut self) -> Result<String, ProtocolError> {
+        match self.read_line()? {
+            Some(text) => Ok(text),
+            None => Err(ProtocolError::UnexpectedEof { line: self.line + 1 }),
+        }
+    }
+}
+
+/// Parses a single turn held in memory, e.g. one produced by the local referee.
+pub fn parse_turn(text: &str) -> Result<GameState, ProtocolError> {
+    match TurnReader::new(text.as_bytes()).read_turn()? {
+        Some(game) => Ok(game),
+        None => Err(ProtocolError::UnexpectedEof { line: 1 }),
+    }
+}
+
+/// One input line split into the fields it is expected to hold.
+struct Line<'a> {
+    text: &'a str,
+    number: usize,
+    fields: &'static [&'static str],
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str, number: usize, fields: &'static [&'static str]) -> Result<Line<'a>, ProtocolError> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+
+        if tokens.len() > fields.len() {
+            return Err(ProtocolError::TrailingInput { line: number, text: text.to_string() });
+        }
+
+        Ok(Line { text, number, fields, tokens })
+    }
+
+    fn field<T: FromStr>(&self, index: usize) -> Result<T, ProtocolError> {
+        match self.tokens.get(index) {
+            Some(token) => token.parse().map_err(|_| self.invalid(index)),
+            None => Err(ProtocolError::MissingField {
+                line: self.number,
+                field: self.fields[index],
+                text: self.text.to_string(),
+            }),
+        }
+    }
+
+    fn invalid(&self, index: usize) -> ProtocolError {
+        ProtocolError::InvalidField {
+            line: self.number,
+            field: self.fields[index],
+            text: self.text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURN: &str = "\
+2
+42 BREW -2 -2 0 0 6 3 4 0 0
+78 CAST 2 0 0 0 0 -1 -1 1 0
+3 0 0 0 0
+3 0 0 0 0
+";
+
+    fn read_turns(text: &str) -> Vec<Result<Option<GameState>, ProtocolError>> {
+        let mut reader = TurnReader::new(text.as_bytes());
+        vec![reader.read_turn(), reader.read_turn()]
+    }
+
+    fn error(text: &str) -> ProtocolError {
+        parse_turn(text).err().expect("input is malformed")
+    }
+
+    #[test]
+    fn reads_a_turn() {
+        let game = parse_turn(TURN).unwrap();
+
+        assert_eq!(game.potions.len(), 1);
+        assert_eq!(game.my_cast.len(), 1);
+        assert_eq!(game.my_ingredients, [3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reports_a_truncated_line() {
+        match error(&TURN.replace("78 CAST 2 0 0 0 0 -1 -1 1 0", "78 CAST 2 0 0 0 0 -1")) {
+            ProtocolError::MissingField { line, field, text } => {
+                assert_eq!((line, field, &text[..]), (3, "taxCount", "78 CAST 2 0 0 0 0 -1"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn reports_a_non_numeric_token() {
+        match error(&TURN.replace("3 0 0 0 0\n3", "3 0 x 0 0\n3")) {
+            ProtocolError::InvalidField { line, field, text } => {
+                assert_eq!((line, field, &text[..]), (4, "inv2", "3 0 x 0 0"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn reports_an_extra_token() {
+        match error(&TURN.replace("42 BREW -2 -2 0 0 6 3 4 0 0", "42 BREW -2 -2 0 0 6 3 4 0 0 7")) {
+            ProtocolError::TrailingInput { line, text } => {
+                assert_eq!((line, &text[..]), (2, "42 BREW -2 -2 0 0 6 3 4 0 0 7"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn reports_eof_in_the_middle_of_a_turn() {
+        let truncated: String = TURN.lines().take(3).map(|line| format!("{}\n", line)).collect();
+
+        match error(&truncated) {
+            ProtocolError::UnexpectedEof { line } => assert_eq!(line, 4),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn ends_the_match_on_eof_between_turns() {
+        let turns = read_turns(TURN);
+
+        assert!(matches!(turns[0], Ok(Some(_))));
+        assert!(matches!(turns[1], Ok(None)));
+    }
+}
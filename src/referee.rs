@@ -0,0 +1,473 @@
+//! Local Witches' Brew rules engine.
+//!
+//! Deals the board, writes the same per-turn input the arena sends and applies both players'
+//! commands, so whole matches can be played without the arena.
+
+use std::cmp::min;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::Action::{Brew, Cast, Learn, Rest, Wait};
+use crate::protocol::parse_turn;
+use crate::{catalog, pay, Action, Bot, BotConfig, Potion, Spell, MAX_BREWS, MAX_INGREDIENTS, MAX_TURNS};
+
+const POTION_SLOTS: usize = 5;
+const TOME_SLOTS: usize = 6;
+const URGENCY_BONUSES: [i32; 2] = [3, 1];
+const URGENCY_BONUS_COUNT: i32 = 4;
+const STARTING_INGREDIENTS: [i32; 4] = [3, 0, 0, 0];
+const STARTING_SPELLS: [[i32; 4]; 4] = [[2, 0, 0, 0], [-1, 1, 0, 0], [0, -1, 1, 0], [0, 0, -1, 1]];
+const FIRST_STARTING_SPELL_ID: i32 = 78;
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub ingredients: [i32; 4],
+    pub rupees: i32,
+    pub spells: Vec<Spell>,
+    pub brew_count: i32,
+    pub invalid_actions: i32,
+}
+
+impl Player {
+    fn new(first_spell_id: i32) -> Player {
+        let spells = STARTING_SPELLS.iter().enumerate().map(|(i, delta)| Spell {
+            id: first_spell_id + i as i32,
+            delta: *delta,
+            read_ahead_tax: -1,
+            tax_count: -1,
+            castable: true,
+            repeatable: false,
+        }).collect();
+
+        Player {
+            ingredients: STARTING_INGREDIENTS,
+            rupees: 0,
+            spells,
+            brew_count: 0,
+            invalid_actions: 0,
+        }
+    }
+
+    /// Rupees plus one per tier-1+ ingredient left, as counted when the game ends.
+    pub fn final_score(&self) -> i32 {
+        self.rupees + self.ingredients[1] + self.ingredients[2] + self.ingredients[3]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MatchResult {
+    pub scores: [i32; 2],
+    pub turns: i32,
+}
+
+pub struct Referee {
+    pub players: [Player; 2],
+    /// Potions on the board, left to right.
+    pub potions: Vec<Potion>,
+    potion_deck: Vec<Potion>,
+    /// Tome spells on the board in tome order. `tax_count` holds the tier-0 pile on the spell.
+    pub tome: Vec<Spell>,
+    tome_deck: Vec<Spell>,
+    urgency_bonus_counts: [i32; 2],
+    next_spell_id: i32,
+    pub turn: i32,
+}
+
+impl Referee {
+    /// A match dealt from the full catalog decks.
+    pub fn new(seed: u64) -> Referee {
+        Referee::with_decks(seed, catalog::potion_deck(), catalog::tome_deck())
+    }
+
+    pub fn with_decks(seed: u64, mut potion_deck: Vec<Potion>, mut tome_deck: Vec<Spell>) -> Referee {
+        let mut rng = StdRng::seed_from_u64(seed);
+        potion_deck.shuffle(&mut rng);
+        tome_deck.shuffle(&mut rng);
+
+        let mut referee = Referee {
+            players: [
+                Player::new(FIRST_STARTING_SPELL_ID),
+                Player::new(FIRST_STARTING_SPELL_ID + STARTING_SPELLS.len() as i32),
+            ],
+            potions: Vec::new(),
+            potion_deck,
+            tome: Vec::new(),
+            tome_deck,
+            urgency_bonus_counts: [URGENCY_BONUS_COUNT; 2],
+            next_spell_id: FIRST_STARTING_SPELL_ID + 2 * STARTING_SPELLS.len() as i32,
+            turn: 0,
+        };
+
+        referee.refill();
+        referee
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.turn >= MAX_TURNS || self.players.iter().any(|player| player.brew_count >= MAX_BREWS)
+    }
+
+    pub fn scores(&self) -> [i32; 2] {
+        [self.players[0].final_score(), self.players[1].final_score()]
+    }
+
+    /// The turn input for `player`, formatted exactly as the arena sends it.
+    pub fn input(&self, player: usize) -> String {
+        let me = &self.players[player];
+        let opp = &self.players[1 - player];
+        let mut lines = Vec::new();
+
+        for (slot, potion) in self.potions.iter().enumerate() {
+            let (bonus, remaining) = match self.urgency_bonus(slot) {
+                Some(i) => (URGENCY_BONUSES[i], self.urgency_bonus_counts[i]),
+                None => (0, 0),
+            };
+
+            lines.push(format!("{} BREW {} {} {} {} {} {} {} 0 0",
+                               potion.id, potion.delta[0], potion.delta[1], potion.delta[2], potion.delta[3],
+                               potion.price + bonus, bonus, remaining));
+        }
+
+        for spell in &me.spells {
+            lines.push(spell_line(spell, "CAST", -1, -1));
+        }
+
+        for spell in &opp.spells {
+            lines.push(spell_line(spell, "OPPONENT_CAST", -1, -1));
+        }
+
+        for (index, spell) in self.tome.iter().enumerate() {
+            lines.push(spell_line(spell, "LEARN", index as i32, spell.tax_count));
+        }
+
+        let mut input = format!("{}\n", lines.len());
+
+        for line in lines {
+            input.push_str(&line);
+            input.push('\n');
+        }
+
+        for player in &[me, opp] {
+            input.push_str(&format!("{} {} {} {} {}\n",
+                                    player.ingredients[0], player.ingredients[1], player.ingredients[2],
+                                    player.ingredients[3], player.rupees));
+        }
+
+        input
+    }
+
+    /// Applies one simultaneous turn. Invalid actions are counted and played as WAIT.
+    pub fn apply(&mut self, actions: [Action; 2]) {
+        let mut actions = actions;
+
+        for (player, action) in actions.iter_mut().enumerate() {
+            if !self.is_valid(player, *action) {
+                eprintln!("Player {} played invalid action {:?} on turn {}", player, action, self.turn + 1);
+                self.players[player].invalid_actions += 1;
+                *action = Wait;
+            }
+        }
+
+        let mut learned_slots = Vec::new();
+        let mut brewed_slots = Vec::new();
+
+        for (player, action) in actions.iter().enumerate() {
+            match *action {
+                Wait => {}
+                Rest => {
+                    for spell in self.players[player].spells.iter_mut() {
+                        spell.castable = true;
+                    }
+                }
+                Cast(id, times) => {
+                    let player = &mut self.players[player];
+                    let spell = player.spells.iter_mut().find(|spell| spell.id == id).unwrap();
+                    let delta = [spell.delta[0] * times, spell.delta[1] * times, spell.delta[2] * times, spell.delta[3] * times];
+
+                    player.ingredients = pay(&delta, &player.ingredients).unwrap();
+                    spell.castable = false;
+                }
+                Learn(id) => {
+                    let slot = self.tome.iter().position(|spell| spell.id == id).unwrap();
+                    let spell = &self.tome[slot];
+                    let player = &mut self.players[player];
+
+                    player.ingredients[0] -= slot as i32;
+                    let total: i32 = player.ingredients.iter().sum();
+                    player.ingredients[0] += min(spell.tax_count, MAX_INGREDIENTS - total);
+
+                    player.spells.push(Spell {
+                        id: self.next_spell_id,
+                        delta: spell.delta,
+                        read_ahead_tax: -1,
+                        tax_count: -1,
+                        castable: true,
+                        repeatable: spell.repeatable,
+                    });
+
+                    self.next_spell_id += 1;
+                    learned_slots.push(slot);
+                }
+                Brew(id) => {
+                    let slot = self.potions.iter().position(|potion| potion.id == id).unwrap();
+                    let potion = &self.potions[slot];
+                    let bonus = self.urgency_bonus(slot).map_or(0, |i| URGENCY_BONUSES[i]);
+                    let player = &mut self.players[player];
+
+                    player.ingredients = pay(&potion.delta, &player.ingredients).unwrap();
+                    player.rupees += potion.price + bonus;
+                    player.brew_count += 1;
+                    brewed_slots.push(slot);
+                }
+            }
+        }
+
+        // The read-ahead tax goes onto every spell to the left of the learned one.
+        for slot in &learned_slots {
+            for spell in self.tome.iter_mut().take(*slot) {
+                spell.tax_count += 1;
+            }
+        }
+
+        brewed_slots.sort_unstable();
+        brewed_slots.dedup();
+
+        let used_bonuses: Vec<usize> = brewed_slots.iter().filter_map(|slot| self.urgency_bonus(*slot)).collect();
+
+        for i in used_bonuses {
+            self.urgency_bonus_counts[i] -= 1;
+        }
+
+        for slot in brewed_slots.iter().rev() {
+            self.potions.remove(*slot);
+        }
+
+        learned_slots.sort_unstable();
+        learned_slots.dedup();
+
+        for slot in learned_slots.iter().rev() {
+            self.tome.remove(*slot);
+        }
+
+        self.refill();
+        self.turn += 1;
+    }
+
+    /// Runs the match to the end, asking each player for an action from its turn input.
+    pub fn play(mut self, first: &mut dyn FnMut(&str) -> Action, second: &mut dyn FnMut(&str) -> Action) -> MatchResult {
+        while !self.is_over() {
+            let actions = [first(&self.input(0)), second(&self.input(1))];
+            self.apply(actions);
+        }
+
+        MatchResult {
+            scores: self.scores(),
+            turns: self.turn,
+        }
+    }
+
+    fn is_valid(&self, player: usize, action: Action) -> bool {
+        let player = &self.players[player];
+
+        match action {
+            Wait | Rest => true,
+            Cast(id, times) => match player.spells.iter().find(|spell| spell.id == id) {
+                Some(spell) => {
+                    let delta = [spell.delta[0] * times, spell.delta[1] * times, spell.delta[2] * times, spell.delta[3] * times];
+
+                    spell.castable && times >= 1 && (times == 1 || spell.repeatable) && pay(&delta, &player.ingredients).is_some()
+                }
+                None => false,
+            },
+            Learn(id) => match self.tome.iter().position(|spell| spell.id == id) {
+                Some(slot) => player.ingredients[0] >= slot as i32,
+                None => false,
+            },
+            Brew(id) => match self.potions.iter().find(|potion| potion.id == id) {
+                Some(potion) => pay(&potion.delta, &player.ingredients).is_some(),
+                None => false,
+            },
+        }
+    }
+
+    /// Index into `URGENCY_BONUSES` of the bonus paid for brewing the potion in `slot`.
+    fn urgency_bonus(&self, slot: usize) -> Option<usize> {
+        match slot {
+            0 if self.urgency_bonus_counts[0] > 0 => Some(0),
+            0 if self.urgency_bonus_counts[1] > 0 => Some(1),
+            1 if self.urgency_bonus_counts[0] > 0 && self.urgency_bonus_counts[1] > 0 => Some(1),
+            _ => None,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.potions.len() < POTION_SLOTS && !self.potion_deck.is_empty() {
+            self.potions.push(self.potion_deck.remove(0));
+        }
+
+        while self.tome.len() < TOME_SLOTS && !self.tome_deck.is_empty() {
+            let mut spell = self.tome_deck.remove(0);
+            spell.tax_count = 0;
+            spell.castable = false;
+            self.tome.push(spell);
+        }
+    }
+}
+
+/// Plays one catalog-dealt match between two in-process bots, the first config in seat 0.
+pub fn play_bots(seed: u64, configs: [&BotConfig; 2]) -> MatchResult {
+    let mut first = Bot::with_config(configs[0].clone());
+    let mut second = Bot::with_config(configs[1].clone());
+
+    Referee::new(seed).play(
+        &mut |input| first.play(parse_turn(input).expect("referee input is well-formed")).action,
+        &mut |input| second.play(parse_turn(input).expect("referee input is well-formed")).action,
+    )
+}
+
+/// Plays `games` matches between configs A and B on `threads` threads and returns each match's
+/// `[A score, B score]`. Every seed from `seed` on is played twice, with the seats swapped.
+pub fn play_series(configs: [&BotConfig; 2], games: usize, seed: u64, threads: usize) -> Vec<[i32; 2]> {
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+
+            scope.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+
+                if game >= games {
+                    break;
+                }
+
+                let seed = seed + (game / 2) as u64;
+                let scores = match game % 2 {
+                    0 => play_bots(seed, configs).scores,
+                    _ => {
+                        let scores = play_bots(seed, [configs[1], configs[0]]).scores;
+                        [scores[1], scores[0]]
+                    }
+                };
+
+                if sender.send(scores).is_err() {
+                    break;
+                }
+            });
+        }
+    });
+
+    drop(sender);
+    receiver.into_iter().collect()
+}
+
+/// The match points of the first side of `scores`: 1 for a win and half of one for a draw.
+pub fn points(scores: [i32; 2]) -> f32 {
+    match scores[0] - scores[1] {
+        margin if margin > 0 => 1.0,
+        0 => 0.5,
+        _ => 0.0,
+    }
+}
+
+fn spell_line(spell: &Spell, action_type: &str, tome_index: i32, tax_count: i32) -> String {
+    format!("{} {} {} {} {} {} 0 {} {} {} {}",
+            spell.id, action_type, spell.delta[0], spell.delta[1], spell.delta[2], spell.delta[3],
+            tome_index, tax_count, spell.castable as i32, spell.repeatable as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn potion(id: i32) -> Potion {
+        Potion { id, delta: [-1, 0, 0, 0], price: 5, urgency_bonus: 0, urgency_bonus_count: 0 }
+    }
+
+    fn tome_spell(id: i32) -> Spell {
+        Spell { id, delta: [0, 0, 0, 1], read_ahead_tax: 0, tax_count: 0, castable: false, repeatable: false }
+    }
+
+    fn referee() -> Referee {
+        let mut referee = Referee::new(1);
+        referee.potions = (42..47).map(potion).collect();
+        referee.tome = (0..6).map(tome_spell).collect();
+        referee
+    }
+
+    #[test]
+    fn learning_pays_tax_onto_the_spells_to_the_left() {
+        let mut referee = referee();
+        referee.players[0].ingredients = [5, 0, 0, 0];
+
+        referee.apply([Learn(2), Wait]);
+
+        assert_eq!(referee.players[0].ingredients, [3, 0, 0, 0]);
+        assert_eq!(referee.players[0].spells.last().unwrap().delta, [0, 0, 0, 1]);
+        // The last slot is refilled from the deck.
+        assert_eq!(referee.tome.iter().take(5).map(|spell| (spell.id, spell.tax_count)).collect::<Vec<_>>(),
+                   vec![(0, 1), (1, 1), (3, 0), (4, 0), (5, 0)]);
+    }
+
+    #[test]
+    fn learning_collects_tax_up_to_a_full_inventory() {
+        let mut referee = referee();
+        referee.tome[0].tax_count = 4;
+        referee.tome[1].tax_count = 1;
+        referee.players[0].ingredients = [2, 2, 2, 2];
+        referee.players[1].ingredients = [2, 0, 0, 0];
+
+        referee.apply([Learn(0), Learn(1)]);
+
+        assert_eq!(referee.players[0].ingredients, [4, 2, 2, 2]);
+        assert_eq!(referee.players[1].ingredients, [2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn urgency_bonus_falls_back_to_the_plus_one_pile() {
+        let mut referee = referee();
+        referee.urgency_bonus_counts = [1, 4];
+        referee.players[0].ingredients = [5, 0, 0, 0];
+
+        referee.apply([Brew(42), Wait]);
+
+        assert_eq!(referee.players[0].rupees, 8);
+        assert_eq!(referee.urgency_bonus_counts, [0, 4]);
+
+        // The +3 pile is gone: the first potion gets +1 and the second nothing.
+        let input = referee.input(0);
+        let brews: Vec<&str> = input.lines().filter(|line| line.contains(" BREW ")).take(2).collect();
+        assert_eq!(brews, vec!["43 BREW -1 0 0 0 6 1 4 0 0", "44 BREW -1 0 0 0 5 0 0 0 0"]);
+
+        referee.apply([Brew(43), Brew(43)]);
+
+        assert_eq!([referee.players[0].rupees, referee.players[1].rupees], [14, 6]);
+        assert_eq!(referee.urgency_bonus_counts, [0, 3]);
+    }
+
+    #[test]
+    fn game_ends_at_turn_100() {
+        let mut referee = referee();
+        referee.turn = MAX_TURNS - 1;
+        assert!(!referee.is_over());
+
+        referee.apply([Wait, Wait]);
+        assert!(referee.is_over());
+    }
+
+    #[test]
+    fn game_ends_at_the_sixth_brew() {
+        let mut referee = referee();
+        referee.players[1].brew_count = MAX_BREWS - 1;
+        assert!(!referee.is_over());
+
+        referee.apply([Wait, Brew(42)]);
+        assert!(referee.is_over());
+    }
+}
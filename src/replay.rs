@@ -0,0 +1,149 @@
+//! Record and replay of turn inputs.
+//!
+//! A recording holds every raw turn input followed by the action the bot chose for it:
+//!
+//! ```text
+//! # turn 1
+//! 21
+//! 42 BREW -2 -2 0 0 9 3 4 0 0
+//! ...
+//! > BREW 42
+//! ```
+//!
+//! Replaying feeds the inputs through the same parsing and search as a live match and reports
+//! every turn where the action differs from the recorded one. Live searches stop on the clock, so
+//! replays stop on a node budget instead, `node_limit` if it is set and `DEFAULT_NODE_BUDGET`
+//! otherwise. Replaying the same log twice then gives the same actions.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use crate::protocol::{parse_turn, ProtocolError};
+use crate::{Action, Bot, BotConfig, DEFAULT_NODE_BUDGET};
+
+const TURN_MARKER: &str = "# turn ";
+const ACTION_MARKER: &str = "> ";
+
+pub struct Recorder {
+    out: Box<dyn Write>,
+    turn: i32,
+}
+
+impl Recorder {
+    pub fn new(out: Box<dyn Write>) -> Recorder {
+        Recorder { out, turn: 0 }
+    }
+
+    pub fn create(path: &str) -> io::Result<Recorder> {
+        Ok(Recorder::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+
+    pub fn record(&mut self, input: &str, action: &Action) -> io::Result<()> {
+        self.turn += 1;
+
+        write!(self.out, "{}{}\n{}", TURN_MARKER, self.turn, input)?;
+        writeln!(self.out, "{}{}", ACTION_MARKER, action)?;
+
+        // Flushed every turn so the log survives the arena killing the process.
+        self.out.flush()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedTurn {
+    pub input: String,
+    pub action: String,
+}
+
+pub fn read_log<R: BufRead>(log: R) -> io::Result<Vec<RecordedTurn>> {
+    let mut turns = Vec::new();
+    let mut input = String::new();
+
+    for line in log.lines() {
+        let line = line?;
+
+        if line.starts_with(TURN_MARKER) {
+            input.clear();
+        } else if let Some(action) = line.strip_prefix(ACTION_MARKER) {
+            turns.push(RecordedTurn {
+                input: input.clone(),
+                action: action.to_string(),
+            });
+        } else {
+            input.push_str(&line);
+            input.push('\n');
+        }
+    }
+
+    Ok(turns)
+}
+
+/// Replays a recording and returns the number of turns whose action changed.
+pub fn replay(turns: &[RecordedTurn], config: &BotConfig) -> Result<usize, ProtocolError> {
+    let nodes = match config.node_limit {
+        0 => DEFAULT_NODE_BUDGET,
+        nodes => nodes,
+    };
+    let mut bot = Bot::with_config(config.clone().with_node_budget(nodes));
+    let mut mismatches = 0;
+
+    for (i, turn) in turns.iter().enumerate() {
+        let action = bot.play(parse_turn(&turn.input)?).action.to_string();
+
+        if action != turn.action {
+            mismatches += 1;
+            println!("Turn {}: recorded {}, replayed {}", i + 1, turn.action, action);
+        }
+    }
+
+    println!("Replayed {} turns, {} changed.", turns.len(), mismatches);
+    Ok(mismatches)
+}
+
+pub fn replay_file(path: &str, config: &BotConfig) -> Result<usize, Box<dyn std::error::Error>> {
+    let turns = read_log(BufReader::new(File::open(path)?))?;
+    Ok(replay(&turns, config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::Action::{Brew, Cast, Rest};
+
+    /// Lets the test read back what the recorder wrote.
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_back_what_was_recorded() {
+        let inputs = ["1\n42 BREW -2 -2 0 0 9 3 4 0 0\n2 2 0 0 0\n3 0 0 0 0\n", "1\n78 CAST 2 0 0 0 0 -1 -1 0 0\n0 0 0 0 9\n3 0 0 0 0\n", "0\n2 0 0 0 9\n3 0 0 0 0\n"];
+        let actions = [Brew(42), Rest, Cast(78, 1)];
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut recorder = Recorder::new(Box::new(Shared(Rc::clone(&log))));
+
+        for (input, action) in inputs.iter().zip(actions.iter()) {
+            recorder.record(input, action).unwrap();
+        }
+
+        let turns = read_log(&log.borrow()[..]).unwrap();
+
+        assert_eq!(turns.len(), 3);
+
+        for (turn, (input, action)) in turns.iter().zip(inputs.iter().zip(actions.iter())) {
+            assert_eq!(turn.input, *input);
+            assert_eq!(turn.action, action.to_string());
+        }
+    }
+}
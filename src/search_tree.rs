@@ -0,0 +1,109 @@
+//! Search tree export to Graphviz.
+//!
+//! With `dot_turn` set, the search of that turn records the nodes it expands, up to `dot_nodes`
+//! of them, and the tree is written to `search-<turn>.dot`. Every node is labelled with the action
+//! leading to it, its depth, `cumulative_score` and `score()`, and the line to the chosen leaf is
+//! drawn in red:
+//!
+//! ```text
+//! cg-fall-2020 --set dot_turn=17 --replay match.log && dot -Tsvg search-17.dot > search-17.svg
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+use crate::Action;
+
+/// The parent of the root, and every node's id when the tree records nothing.
+pub const UNRECORDED: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+struct TreeNode {
+    parent: usize,
+    action: Action,
+    depth: i32,
+    cumulative_score: f32,
+    score: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchTree {
+    capacity: usize,
+    nodes: Vec<TreeNode>,
+    principal: usize,
+}
+
+impl SearchTree {
+    /// A tree recording at most `capacity` nodes, none at all when it's 0.
+    pub fn new(capacity: usize) -> SearchTree {
+        SearchTree {
+            capacity,
+            nodes: Vec::new(),
+            principal: UNRECORDED,
+        }
+    }
+
+    /// Whether a node added now would be recorded, so callers can skip working out its label.
+    pub fn has_room(&self) -> bool {
+        self.nodes.len() < self.capacity
+    }
+
+    /// Records a node below `parent`, `UNRECORDED` for the root, and returns its id. Once the tree
+    /// is full `parent` is returned instead, so whatever lies below a node that didn't fit, the
+    /// principal line included, is cut off at its closest recorded ancestor.
+    pub fn add(&mut self, parent: usize, action: Action, depth: i32, cumulative_score: f32, score: f32) -> usize {
+        if !self.has_room() {
+            return parent;
+        }
+
+        self.nodes.push(TreeNode { parent, action, depth, cumulative_score, score });
+        self.nodes.len() - 1
+    }
+
+    /// Highlights the line from the root to `leaf`.
+    pub fn set_principal(&mut self, leaf: usize) {
+        self.principal = leaf;
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut on_line = vec![false; self.nodes.len()];
+        let mut node = self.principal;
+
+        while node != UNRECORDED {
+            on_line[node] = true;
+            node = self.nodes[node].parent;
+        }
+
+        let mut dot = String::from("digraph search {\n    node [shape=box, fontname=monospace];\n");
+
+        // Writing to a String can't fail.
+        for (id, node) in self.nodes.iter().enumerate() {
+            let label = match node.parent {
+                UNRECORDED => "ROOT".to_string(),
+                _ => node.action.to_string(),
+            };
+            let style = match on_line[id] {
+                true => "color=red, penwidth=2",
+                false => "color=black",
+            };
+
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{}\\ndepth {}\\ncumulative {:.1}\\nscore {:.1}\", {}];",
+                id, label, node.depth, node.cumulative_score, node.score, style
+            );
+
+            if node.parent != UNRECORDED {
+                let _ = writeln!(dot, "    n{} -> n{} [{}];", node.parent, id, style);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_dot())
+    }
+}
@@ -0,0 +1,74 @@
+//! Per-turn search telemetry.
+//!
+//! With `telemetry` set, every turn ends with one JSON object on its own stderr line, so the logs
+//! of many matches can be filtered down to lines starting with `{` and aggregated by scripts:
+//!
+//! ```text
+//! {"turn":12,"inventory":[3,1,0,2],"candidates":[{"action":"BREW 42","score":18.5}],"nodes":5120,"depth":4,"max_width":31,"elapsed_us":40210}
+//! ```
+//!
+//! `elapsed_us` is `SearchStats::elapsed`, the search alone. The JSON is written by hand, the crate
+//! has no serde.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::{Action, GameState};
+
+/// What a search reports about itself, filled in as it goes.
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    pub nodes: usize,
+    /// The deepest complete layer, or the deepest node for MCTS.
+    pub depth: i32,
+    /// The most children of any single node.
+    pub max_width: usize,
+    /// Time spent searching, without the parsing and `Bot::observe` before it. The turn's time
+    /// budget runs from `GameState::received_at` instead, see `timing`.
+    pub elapsed: Duration,
+    /// Every root action with the best score found below it, in the order they were first seen.
+    candidates: Vec<(Action, f32)>,
+}
+
+impl SearchStats {
+    /// Records a node reached through `action` at the root, keeping the best score per action.
+    pub fn candidate(&mut self, action: Action, score: f32) {
+        match self.candidates.iter_mut().find(|(candidate, _)| *candidate == action) {
+            Some((_, best)) => *best = best.max(score),
+            None => self.candidates.push((action, score)),
+        }
+    }
+
+    /// The telemetry record of the turn `game` was searched for.
+    pub fn to_json(&self, game: &GameState) -> String {
+        let mut json = String::new();
+        let inventory = game.my_ingredients;
+
+        // Writing to a String can't fail.
+        let _ = write!(json, "{{\"turn\":{},\"inventory\":[{},{},{},{}],\"candidates\":[", game.turn_count, inventory[0], inventory[1], inventory[2], inventory[3]);
+
+        for (i, (action, score)) in self.candidates.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+
+            let _ = write!(json, "{{\"action\":\"{}\",\"score\":{}}}", action, number(*score));
+        }
+
+        let _ = write!(
+            json,
+            "],\"nodes\":{},\"depth\":{},\"max_width\":{},\"elapsed_us\":{}}}",
+            self.nodes, self.depth, self.max_width, self.elapsed.as_micros()
+        );
+
+        json
+    }
+}
+
+/// JSON has no infinities or NaN.
+fn number(value: f32) -> String {
+    match value.is_finite() {
+        true => value.to_string(),
+        false => "null".to_string(),
+    }
+}
@@ -0,0 +1,37 @@
+//! Per-turn time budget.
+//!
+//! The arena allows 1000ms for the first turn and 50ms for every later one, counted from the moment
+//! it sends the turn input. The clock therefore starts when `TurnReader` receives the first line of
+//! a turn, so parsing and the opponent analysis are paid for out of the same budget as the search.
+
+use std::time::{Duration, Instant};
+
+use crate::{BotConfig, GameState};
+
+#[derive(Debug, Copy, Clone)]
+pub struct TimeManager {
+    first_turn_limit: Duration,
+    turn_limit: Duration,
+}
+
+impl TimeManager {
+    pub fn new(config: &BotConfig) -> TimeManager {
+        TimeManager {
+            first_turn_limit: Duration::from_millis(config.first_turn_time_limit),
+            turn_limit: Duration::from_millis(config.time_limit),
+        }
+    }
+
+    /// The time we allow ourselves on `turn`, counting from 1.
+    pub fn budget(&self, turn: i32) -> Duration {
+        match turn {
+            1 => self.first_turn_limit,
+            _ => self.turn_limit,
+        }
+    }
+
+    /// When the search for this turn has to stop.
+    pub fn deadline(&self, game: &GameState) -> Instant {
+        game.received_at + self.budget(game.turn_count)
+    }
+}
@@ -0,0 +1,320 @@
+//! Inventory transition graph.
+//!
+//! There are only 1001 inventories holding at most 10 ingredients. For a given spellbook we
+//! precompute where every spell, cast any number of times in one go, takes each of them. Casting
+//! during the search is then a lookup, and the exact number of casts between an inventory and a
+//! potion's cost is a breadth-first search over a small graph.
+
+use std::collections::VecDeque;
+
+use crate::inventory::{Delta, Ingredients};
+use crate::{Spell, MAX_INGREDIENTS};
+
+/// Inventories holding at most 10 ingredients across 4 tiers.
+pub const INVENTORY_COUNT: usize = 1001;
+/// `casts_to_afford` distance of an inventory that cannot reach the cost at all.
+pub const UNREACHABLE: u8 = u8::MAX;
+
+/// Every tier count from 0 to 10, so `slot` is a mixed-radix number.
+const SLOT_COUNT: usize = 11 * 11 * 11 * 11;
+const NONE: u16 = u16::MAX;
+
+#[derive(Debug, Clone)]
+pub struct Transitions {
+    spell_ids: Vec<i32>,
+    inventories: Vec<Ingredients>,
+    /// The index in `inventories` of each `slot`.
+    indices: Vec<u16>,
+    /// Moves `first_move[s]..first_move[s + 1]` belong to spell `s`, the `k`th one casting it `k + 1` times.
+    first_move: Vec<usize>,
+    /// `next[i * move_count + m]` is where move `m` takes inventory `i`, `NONE` if it can't be cast
+    /// there. The moves of an inventory are side by side since the searches go through them together.
+    next: Vec<u16>,
+    move_count: usize,
+    /// `previous[i]` lists the inventories one cast away from inventory `i`.
+    previous: Vec<Vec<u16>>,
+}
+
+impl Transitions {
+    pub fn build(spells: &[Spell]) -> Transitions {
+        let mut inventories = Vec::with_capacity(INVENTORY_COUNT);
+        let mut indices = vec![NONE; SLOT_COUNT];
+
+        for total in 0..=MAX_INGREDIENTS {
+            for tier_3 in 0..=total {
+                for tier_2 in 0..=total - tier_3 {
+                    for tier_1 in 0..=total - tier_3 - tier_2 {
+                        let ingredients = Ingredients::pack(&[total - tier_3 - tier_2 - tier_1, tier_1, tier_2, tier_3]);
+                        indices[slot(ingredients)] = inventories.len() as u16;
+                        inventories.push(ingredients);
+                    }
+                }
+            }
+        }
+
+        let mut first_move = Vec::with_capacity(spells.len() + 1);
+        let mut rows: Vec<Vec<u16>> = Vec::new();
+
+        for spell in spells {
+            first_move.push(rows.len());
+            let delta = Delta::pack(&spell.delta);
+
+            for times in 1..=MAX_INGREDIENTS {
+                let row: Vec<u16> = inventories.iter()
+                    .map(|ingredients| ingredients.pay(delta.times(times)).map_or(NONE, |paid| indices[slot(paid)]))
+                    .collect();
+
+                // Casting more times costs more, so no inventory affords the next count either.
+                if row.iter().all(|index| *index == NONE) {
+                    break;
+                }
+
+                rows.push(row);
+
+                if !spell.repeatable {
+                    break;
+                }
+            }
+        }
+
+        let move_count = rows.len();
+        first_move.push(move_count);
+
+        let mut next = vec![NONE; INVENTORY_COUNT * move_count];
+        let mut previous = vec![Vec::new(); INVENTORY_COUNT];
+
+        for (m, row) in rows.iter().enumerate() {
+            for (i, index) in row.iter().enumerate() {
+                next[i * move_count + m] = *index;
+
+                if *index != NONE {
+                    previous[*index as usize].push(i as u16);
+                }
+            }
+        }
+
+        Transitions {
+            spell_ids: spells.iter().map(|spell| spell.id).collect(),
+            inventories,
+            indices,
+            first_move,
+            next,
+            move_count,
+            previous,
+        }
+    }
+
+    /// Whether the table was built for exactly these spells, in this order.
+    pub fn matches(&self, spells: &[Spell]) -> bool {
+        self.spell_ids.iter().eq(spells.iter().map(|spell| &spell.id))
+    }
+
+    pub fn index(&self, ingredients: Ingredients) -> usize {
+        self.indices[slot(ingredients)] as usize
+    }
+
+    /// The inventory left after casting `spells[spell]` `times` times, like `Ingredients::pay`.
+    pub fn cast(&self, spell: usize, times: i32, ingredients: Ingredients) -> Option<Ingredients> {
+        if times < 1 || self.first_move[spell] + times as usize > self.first_move[spell + 1] {
+            return None;
+        }
+
+        let move_index = self.first_move[spell] + times as usize - 1;
+
+        match self.next[self.index(ingredients) * self.move_count + move_index] {
+            NONE => None,
+            index => Some(self.inventories[index as usize]),
+        }
+    }
+
+    /// For every inventory, by `index`, the fewest casts that reach an inventory paying `cost`.
+    /// Exhausting spells and resting are not modelled, so this is a lower bound on the turns needed.
+    pub fn casts_to_afford(&self, cost: &[i32; 4]) -> Vec<u8> {
+        let cost = Delta::pack(cost);
+        let mut distances = vec![UNREACHABLE; INVENTORY_COUNT];
+        let mut queue = VecDeque::new();
+
+        for (i, ingredients) in self.inventories.iter().enumerate() {
+            if ingredients.pay(cost).is_some() {
+                distances[i] = 0;
+                queue.push_back(i);
+            }
+        }
+
+        while let Some(i) = queue.pop_front() {
+            for previous in &self.previous[i] {
+                let previous = *previous as usize;
+
+                if distances[previous] == UNREACHABLE {
+                    distances[previous] = (distances[i] + 1).min(UNREACHABLE - 1);
+                    queue.push_back(previous);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// `casts_to_afford` for `cost`, together with every inventory's moves ordered by how close to
+    /// the cost they get.
+    pub fn cost_map(&self, cost: &[i32; 4]) -> CostMap {
+        let distances = self.casts_to_afford(cost);
+        let mut offsets = Vec::with_capacity(INVENTORY_COUNT + 1);
+        let mut moves = Vec::new();
+
+        for i in 0..INVENTORY_COUNT {
+            offsets.push(moves.len());
+            let start = moves.len();
+
+            for spell in 0..self.spell_ids.len().min(64) {
+                for move_index in self.first_move[spell]..self.first_move[spell + 1] {
+                    let next = self.next[i * self.move_count + move_index];
+
+                    if next != NONE && distances[next as usize] != UNREACHABLE {
+                        moves.push((next, spell as u8));
+                    }
+                }
+            }
+
+            moves[start..].sort_by_key(|(next, _)| distances[*next as usize]);
+        }
+
+        offsets.push(moves.len());
+        CostMap { distances, offsets, moves }
+    }
+
+    /// The fewest turns from `start` until the cost of `map` can be paid, resting included. Spell
+    /// `s` can't be cast before a REST while bit `s` of `exhausted` is set. `None` if it takes
+    /// more than `horizon` turns.
+    ///
+    /// Iterative deepening A*: the cast distance ignores exhaustion so it never overestimates, and
+    /// it is usually exact or close, which keeps the search tiny. The horizon bounds the work, so
+    /// the answer is always exact.
+    pub fn turns_to_afford(&self, start: Ingredients, exhausted: u64, map: &CostMap, horizon: u8) -> Option<u8> {
+        let start = self.index(start);
+
+        (map.distances[start]..=horizon).find(|bound| map.afford_within(start, exhausted, 0, *bound))
+    }
+}
+
+/// How far each inventory is from one cost, in casts, and how to get closer.
+#[derive(Debug, Clone)]
+pub struct CostMap {
+    /// By inventory index, see `Transitions::casts_to_afford`.
+    pub distances: Vec<u8>,
+    /// `moves[offsets[i]..offsets[i + 1]]` are the casts out of inventory `i`, nearest first.
+    offsets: Vec<usize>,
+    /// The inventory a cast leads to and the spell cast.
+    moves: Vec<(u16, u8)>,
+}
+
+impl CostMap {
+    fn afford_within(&self, index: usize, exhausted: u64, turns: u8, bound: u8) -> bool {
+        if self.distances[index] == 0 {
+            return true;
+        }
+
+        if turns + self.distances[index] > bound {
+            return false;
+        }
+
+        for (next, spell) in &self.moves[self.offsets[index]..self.offsets[index + 1]] {
+            // Moves are nearest first, so the rest can't make it either.
+            if turns + 1 + self.distances[*next as usize] > bound {
+                break;
+            }
+
+            if exhausted & (1 << spell) == 0 && self.afford_within(*next as usize, exhausted | 1 << spell, turns + 1, bound) {
+                return true;
+            }
+        }
+
+        exhausted != 0 && self.afford_within(index, 0, turns + 1, bound)
+    }
+}
+
+fn slot(ingredients: Ingredients) -> usize {
+    (0..4).rev().fold(0, |slot, tier| slot * 11 + ingredients.tier(tier) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use super::*;
+    use crate::catalog::{POTIONS, TOME_SPELLS};
+
+    fn spell(id: i32, delta: [i32; 4], repeatable: bool) -> Spell {
+        Spell { id, delta, read_ahead_tax: -1, tax_count: -1, castable: true, repeatable }
+    }
+
+    /// Breadth-first over inventories and exhausted spells, the obvious way.
+    fn brute_force(spells: &[Spell], start: Ingredients, cost: Delta, horizon: u8) -> Option<u8> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert((start, 0));
+        queue.push_back((start, 0u64, 0));
+
+        while let Some((ingredients, exhausted, turns)) = queue.pop_front() {
+            if ingredients.pay(cost).is_some() {
+                return Some(turns);
+            }
+
+            if turns == horizon {
+                continue;
+            }
+
+            let mut next = Vec::new();
+
+            for (s, spell) in spells.iter().enumerate().filter(|(s, _)| exhausted & (1 << s) == 0) {
+                for times in 1..=MAX_INGREDIENTS {
+                    if let Some(paid) = ingredients.pay(Delta::pack(&spell.delta).times(times)) {
+                        next.push((paid, exhausted | 1 << s));
+                    }
+
+                    if !spell.repeatable {
+                        break;
+                    }
+                }
+            }
+
+            if exhausted != 0 {
+                next.push((ingredients, 0));
+            }
+
+            for (ingredients, exhausted) in next {
+                if seen.insert((ingredients, exhausted)) {
+                    queue.push_back((ingredients, exhausted, turns + 1));
+                }
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn turns_to_afford_is_exact() {
+        let mut spells = vec![
+            spell(78, [2, 0, 0, 0], false),
+            spell(79, [-1, 1, 0, 0], false),
+            spell(80, [0, -1, 1, 0], false),
+            spell(81, [0, 0, -1, 1], false),
+        ];
+        spells.extend([0, 1, 18].iter().map(|&i| spell(90 + i as i32, TOME_SPELLS[i].delta, TOME_SPELLS[i].repeatable)));
+
+        let transitions = Transitions::build(&spells);
+
+        // Every fourth potion keeps the test quick in debug builds.
+        for potion in POTIONS.iter().step_by(4) {
+            let map = transitions.cost_map(&potion.delta);
+
+            for ingredients in &transitions.inventories {
+                assert_eq!(
+                    transitions.turns_to_afford(*ingredients, 0, &map, 4),
+                    brute_force(&spells, *ingredients, Delta::pack(&potion.delta), 4),
+                    "potion {} from {:?}", potion.id, ingredients.unpack()
+                );
+            }
+        }
+    }
+}
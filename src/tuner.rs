@@ -0,0 +1,285 @@
+//! Evolutionary tuning of the evaluation weights.
+//!
+//! A simple genetic algorithm: every generation each candidate weight vector plays a series of
+//! self-play matches against the baseline bot, the best quarter survives unchanged and the rest is
+//! bred from tournament-selected parents with uniform crossover and log-normal mutation.
+//!
+//! The population is checkpointed after every evaluation so an interrupted run resumes where it
+//! stopped.
+
+use std::fs;
+use std::io;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::params::EvalParams;
+use crate::referee;
+use crate::BotConfig;
+
+const TOURNAMENT_SIZE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct TunerConfig {
+    pub population: usize,
+    pub generations: usize,
+    /// Matches each candidate plays against the baseline per generation.
+    pub games: usize,
+    /// Standard deviation of the log-normal mutation applied to each weight.
+    pub sigma: f32,
+    pub seed: u64,
+    pub threads: usize,
+    pub checkpoint: String,
+    /// Settings shared by the candidates and the baseline, e.g. the node budget. With the searches
+    /// on a node budget a candidate's fitness only depends on its weights and the seed.
+    pub bot: BotConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub weights: Vec<f32>,
+    /// Average points per match against the baseline, `None` until evaluated this generation.
+    pub fitness: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Population {
+    pub generation: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Population {
+    /// The default weights plus mutated copies of them.
+    pub fn new(config: &TunerConfig) -> Population {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let defaults = EvalParams::default().to_vec();
+
+        let candidates = (0..config.population).map(|i| Candidate {
+            weights: if i == 0 { defaults.clone() } else { mutate(&defaults, config.sigma, &mut rng) },
+            fitness: None,
+        }).collect();
+
+        Population { generation: 0, candidates }
+    }
+
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.iter()
+            .filter(|candidate| candidate.fitness.is_some())
+            .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+    }
+
+    /// Checkpoint format: a header with the generation and weight keys, then one line per
+    /// candidate holding its fitness (`-` if not evaluated yet) and its weights.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = format!("generation {}\nkeys {}\n", self.generation, EvalParams::KEYS.join(" "));
+
+        for candidate in &self.candidates {
+            let fitness = candidate.fitness.map_or("-".to_string(), |fitness| fitness.to_string());
+            let weights: Vec<String> = candidate.weights.iter().map(|weight| weight.to_string()).collect();
+            text.push_str(&format!("{} {}\n", fitness, weights.join(" ")));
+        }
+
+        // Written to a temporary file first so an interruption never leaves a truncated checkpoint.
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn load(path: &str) -> Result<Population, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+        let mut lines = text.lines();
+
+        let generation = lines.next()
+            .and_then(|line| line.strip_prefix("generation "))
+            .and_then(|generation| generation.trim().parse().ok())
+            .ok_or_else(|| format!("{}: missing generation header", path))?;
+
+        let keys: Vec<&str> = lines.next()
+            .and_then(|line| line.strip_prefix("keys "))
+            .ok_or_else(|| format!("{}: missing keys header", path))?
+            .split_whitespace()
+            .collect();
+
+        if keys != EvalParams::KEYS {
+            return Err(format!("{}: checkpoint weights do not match the current EvalParams", path));
+        }
+
+        let mut candidates = Vec::new();
+
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let mut tokens = line.split_whitespace();
+            let fitness = match tokens.next() {
+                Some("-") => None,
+                Some(fitness) => Some(fitness.parse().map_err(|_| format!("{}: invalid fitness in {:?}", path, line))?),
+                None => continue,
+            };
+            let weights = tokens.map(|weight| weight.parse()).collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| format!("{}: invalid weight in {:?}", path, line))?;
+
+            candidates.push(Candidate { weights, fitness });
+        }
+
+        Ok(Population { generation, candidates })
+    }
+}
+
+/// Runs the tuner from the checkpoint if there is one, otherwise from the default weights.
+pub fn tune(config: &TunerConfig) -> Result<Population, String> {
+    let mut population = match fs::metadata(&config.checkpoint) {
+        Ok(_) => {
+            let population = Population::load(&config.checkpoint)?;
+            eprintln!("Resuming generation {} from {}", population.generation, config.checkpoint);
+            population
+        }
+        Err(_) => Population::new(config),
+    };
+
+    if population.candidates.is_empty() {
+        return Err("the population is empty".to_string());
+    }
+
+    let save = |population: &Population| population.save(&config.checkpoint)
+        .map_err(|error| format!("cannot write {}: {}", config.checkpoint, error));
+
+    loop {
+        // Every candidate of a generation plays the same deals.
+        let seed = config.seed.wrapping_add(population.generation as u64 * config.games as u64);
+
+        for i in 0..population.candidates.len() {
+            if population.candidates[i].fitness.is_some() {
+                continue;
+            }
+
+            let fitness = evaluate(&population.candidates[i].weights, seed, config);
+            population.candidates[i].fitness = Some(fitness);
+            save(&population)?;
+        }
+
+        let best = population.best().unwrap();
+        println!("Generation {}: best fitness {:.3}", population.generation, best.fitness.unwrap());
+
+        // The last generation is kept evaluated so its best candidate can be reported.
+        if population.generation + 1 >= config.generations {
+            break;
+        }
+
+        population = next_generation(&population, config);
+        save(&population)?;
+    }
+
+    Ok(population)
+}
+
+/// Average points per match against the baseline, counting a draw as half a win.
+fn evaluate(weights: &[f32], seed: u64, config: &TunerConfig) -> f32 {
+    let mut candidate = config.bot.clone();
+    candidate.params = EvalParams::from_vec(weights);
+
+    let results = referee::play_series([&candidate, &config.bot], config.games, seed, config.threads);
+    let points: f32 = results.iter().map(|scores| referee::points(*scores)).sum();
+
+    points / results.len().max(1) as f32
+}
+
+fn next_generation(population: &Population, config: &TunerConfig) -> Population {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ (population.generation as u64 + 1));
+
+    let mut ranked = population.candidates.clone();
+    ranked.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+
+    let elite = (ranked.len() / 4).max(1);
+
+    // Survivors are evaluated again on the next generation's deals so a lucky run doesn't stick.
+    let mut candidates: Vec<Candidate> = ranked.iter().take(elite).map(|candidate| Candidate {
+        weights: candidate.weights.clone(),
+        fitness: None,
+    }).collect();
+
+    while candidates.len() < config.population {
+        let first = tournament(&ranked, &mut rng);
+        let second = tournament(&ranked, &mut rng);
+        let child: Vec<f32> = first.weights.iter().zip(&second.weights)
+            .map(|(a, b)| if rng.gen::<bool>() { *a } else { *b })
+            .collect();
+
+        candidates.push(Candidate {
+            weights: mutate(&child, config.sigma, &mut rng),
+            fitness: None,
+        });
+    }
+
+    Population {
+        generation: population.generation + 1,
+        candidates,
+    }
+}
+
+fn tournament<'a>(ranked: &'a [Candidate], rng: &mut StdRng) -> &'a Candidate {
+    (0..TOURNAMENT_SIZE)
+        .map(|_| &ranked[rng.gen_range(0, ranked.len())])
+        .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+        .unwrap()
+}
+
+/// Scales every weight by `exp(sigma * N(0, 1))`, so weights keep their sign and magnitude order.
+fn mutate(weights: &[f32], sigma: f32, rng: &mut StdRng) -> Vec<f32> {
+    weights.iter().map(|weight| weight * (sigma * standard_normal(rng)).exp()).collect()
+}
+
+/// Box-Muller transform.
+fn standard_normal(rng: &mut StdRng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON, 1.0);
+    let u2: f32 = rng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+
+    fn checkpoint(name: &str) -> String {
+        env::temp_dir().join(format!("tuner-{}-{}.txt", process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let path = checkpoint("round-trip");
+        let population = Population {
+            generation: 3,
+            candidates: vec![
+                Candidate { weights: EvalParams::default().to_vec(), fitness: Some(0.625) },
+                Candidate { weights: vec![0.5; EvalParams::KEYS.len()], fitness: None },
+            ],
+        };
+
+        population.save(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = Population::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Unevaluated candidates are marked with `-`.
+        assert!(text.lines().nth(3).unwrap().starts_with("- "));
+        assert_eq!(loaded.generation, 3);
+        assert_eq!(loaded.candidates.len(), 2);
+
+        for (loaded, saved) in loaded.candidates.iter().zip(&population.candidates) {
+            assert_eq!(loaded.weights, saved.weights);
+            assert_eq!(loaded.fitness, saved.fitness);
+        }
+    }
+
+    #[test]
+    fn rejects_a_checkpoint_with_other_weights() {
+        let path = checkpoint("other-keys");
+        fs::write(&path, "generation 1\nkeys brew_weight\n- 80\n").unwrap();
+
+        let result = Population::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.unwrap_err().contains("do not match"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Hand-made positions with an obvious best move, so changes to `score()` that lose sight of it
+//! show up as test failures.
+//!
+//! Positions are turn inputs in the arena's format, dealt from the real potion and tome cards.
+//! Searches stop on a node budget rather than the clock, which keeps them deterministic however
+//! slow the machine running the tests.
+
+use cg_fall_2020::protocol::parse_turn;
+use cg_fall_2020::{get_best_action, Bot, BotConfig, DEFAULT_NODE_BUDGET};
+
+fn config() -> BotConfig {
+    BotConfig { verbose: false, ..BotConfig::default() }.with_node_budget(DEFAULT_NODE_BUDGET)
+}
+
+/// Searches the position `input` as if it were turn `turn` and checks the action is one of
+/// `acceptable`.
+fn assert_plays(turn: i32, input: &str, acceptable: &[&str]) {
+    let config = config();
+    let mut bot = Bot::with_config(config.clone());
+    let mut game = parse_turn(input).expect("position is well-formed");
+
+    bot.set_turn(turn);
+    bot.observe(&mut game);
+
+    let action = get_best_action(&game, &config).action.to_string();
+    assert!(acceptable.contains(&action.as_str()), "played {}, expected one of {:?}", action, acceptable);
+}
+
+/// We can brew 62 and 50 one after the other and would rather take the pricier 62 first, but the
+/// opponent holds the ingredients for 50, so it won't be there next turn.
+#[test]
+fn brews_before_the_opponent_takes_the_potion() {
+    let input = "\
+15
+62 BREW 0 -2 0 -3 19 3 4 0 0
+50 BREW -2 0 0 -2 11 1 4 0 0
+61 BREW 0 0 0 -4 16 0 0 0 0
+63 BREW 0 0 -3 -2 17 0 0 0 0
+64 BREW 0 0 -2 -3 18 0 0 0 0
+78 CAST 2 0 0 0 0 -1 -1 1 0
+79 CAST -1 1 0 0 0 -1 -1 1 0
+80 CAST 0 -1 1 0 0 -1 -1 1 0
+81 CAST 0 0 -1 1 0 -1 -1 1 0
+82 OPPONENT_CAST 2 0 0 0 0 -1 -1 1 0
+83 OPPONENT_CAST -1 1 0 0 0 -1 -1 1 0
+84 OPPONENT_CAST 0 -1 1 0 0 -1 -1 1 0
+85 OPPONENT_CAST 0 0 -1 1 0 -1 -1 1 0
+23 LEARN 1 -3 1 1 0 0 0 0 1
+11 LEARN -4 0 2 0 0 1 0 0 1
+2 2 0 5 20
+2 0 0 2 20
+";
+
+    assert_plays(30, input, &["BREW 50"]);
+}
+
+/// Every spell is exhausted and no potion is in reach. Learning is possible but only a REST gets
+/// the spellbook working again.
+#[test]
+fn rests_when_every_spell_is_exhausted() {
+    let input = "\
+19
+50 BREW -2 0 0 -2 13 3 4 0 0
+61 BREW 0 0 0 -4 17 1 4 0 0
+62 BREW 0 -2 0 -3 16 0 0 0 0
+63 BREW 0 0 -3 -2 17 0 0 0 0
+64 BREW 0 0 -2 -3 18 0 0 0 0
+78 CAST 2 0 0 0 0 -1 -1 0 0
+79 CAST -1 1 0 0 0 -1 -1 0 0
+80 CAST 0 -1 1 0 0 -1 -1 0 0
+81 CAST 0 0 -1 1 0 -1 -1 0 0
+82 OPPONENT_CAST 2 0 0 0 0 -1 -1 1 0
+83 OPPONENT_CAST -1 1 0 0 0 -1 -1 1 0
+84 OPPONENT_CAST 0 -1 1 0 0 -1 -1 1 0
+85 OPPONENT_CAST 0 0 -1 1 0 -1 -1 1 0
+9 LEARN 2 -3 2 0 0 0 0 0 1
+26 LEARN 1 1 1 -1 0 1 0 0 1
+5 LEARN 2 3 -2 0 0 2 0 0 1
+31 LEARN 0 3 2 -2 0 3 0 0 1
+7 LEARN 3 0 1 -1 0 4 0 0 1
+32 LEARN 1 1 3 -2 0 5 0 0 1
+3 2 1 0 20
+3 2 1 0 20
+";
+
+    assert_plays(30, input, &["REST"]);
+}
+
+/// The first tome spell is free, but the second pays out the three ingredients piled on it for the
+/// one it costs. Without the pile, the free one would do.
+#[test]
+fn learns_the_spell_with_tax_on_it() {
+    let input = "\
+19
+52 BREW -3 0 0 -2 14 3 4 0 0
+61 BREW 0 0 0 -4 17 1 4 0 0
+62 BREW 0 -2 0 -3 16 0 0 0 0
+63 BREW 0 0 -3 -2 17 0 0 0 0
+64 BREW 0 0 -2 -3 18 0 0 0 0
+78 CAST 2 0 0 0 0 -1 -1 1 0
+79 CAST -1 1 0 0 0 -1 -1 1 0
+80 CAST 0 -1 1 0 0 -1 -1 1 0
+81 CAST 0 0 -1 1 0 -1 -1 1 0
+82 OPPONENT_CAST 2 0 0 0 0 -1 -1 1 0
+83 OPPONENT_CAST -1 1 0 0 0 -1 -1 1 0
+84 OPPONENT_CAST 0 -1 1 0 0 -1 -1 1 0
+85 OPPONENT_CAST 0 0 -1 1 0 -1 -1 1 0
+15 LEARN 0 2 0 0 0 0 0 0 0
+14 LEARN 0 0 0 1 0 1 3 0 0
+11 LEARN -4 0 2 0 0 2 0 0 1
+28 LEARN 4 1 -1 0 0 3 0 0 1
+39 LEARN 0 0 -2 2 0 4 0 0 1
+16 LEARN 1 0 1 0 0 5 0 0 0
+3 0 0 0 0
+3 0 0 0 0
+";
+
+    assert_plays(3, input, &["LEARN 14"]);
+}
+
+/// Casting the learned repeatable spell three times in one turn leaves exactly the recipe's
+/// ingredients.
+#[test]
+fn repeat_casts_to_reach_the_recipe() {
+    let input = "\
+14
+51 BREW -2 0 -3 0 14 3 4 0 0
+61 BREW 0 0 0 -4 17 1 4 0 0
+62 BREW 0 -2 0 -3 16 0 0 0 0
+63 BREW 0 0 -3 -2 17 0 0 0 0
+64 BREW 0 0 -2 -3 18 0 0 0 0
+78 CAST 2 0 0 0 0 -1 -1 0 0
+79 CAST -1 1 0 0 0 -1 -1 0 0
+80 CAST 0 -1 1 0 0 -1 -1 0 0
+81 CAST 0 0 -1 1 0 -1 -1 0 0
+86 CAST -2 0 1 0 0 -1 -1 1 1
+82 OPPONENT_CAST 2 0 0 0 0 -1 -1 1 0
+83 OPPONENT_CAST -1 1 0 0 0 -1 -1 1 0
+84 OPPONENT_CAST 0 -1 1 0 0 -1 -1 1 0
+85 OPPONENT_CAST 0 0 -1 1 0 -1 -1 1 0
+8 0 0 0 20
+0 0 0 0 20
+";
+
+    assert_plays(30, input, &["CAST 86 3"]);
+}